@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use super::{Suggestion, SuggestionSource};
+use crate::config::{config_dir, AiProvider};
+
+const STATS_FILE: &str = "suggestion_stats.json";
+
+/// Suggestions replaced faster than this were never really seen
+const MIN_VISIBLE: Duration = Duration::from_millis(1000);
+/// Weight of the built-in confidence against observed outcomes, in
+/// suggestions: a bucket needs about this many before its data dominates
+const PRIOR_WEIGHT: f32 = 10.0;
+
+/// Suggestions are grouped by where they came from and how confident the
+/// engine that produced them was, in tenths
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BucketKey {
+    pub source: SuggestionSource,
+    pub provider: AiProvider,
+    pub bucket: u8,
+}
+
+impl BucketKey {
+    fn new(source: SuggestionSource, provider: AiProvider, confidence: f32) -> Self {
+        Self {
+            source,
+            provider,
+            bucket: (confidence.clamp(0.0, 0.99) * 10.0) as u8,
+        }
+    }
+
+    /// Midpoint of the confidence range this bucket covers
+    pub fn confidence(&self) -> f32 {
+        self.bucket as f32 / 10.0 + 0.05
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BucketStats {
+    pub shown: u32,
+    pub accepted: u32,
+    pub partial: u32,
+    pub ignored: u32,
+}
+
+impl BucketStats {
+    /// Share of shown suggestions that were used, counting partial use as half
+    pub fn acceptance_rate(&self) -> Option<f32> {
+        if self.shown == 0 {
+            return None;
+        }
+        Some((self.accepted as f32 + 0.5 * self.partial as f32) / self.shown as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Accepted,
+    Partial,
+    Ignored,
+}
+
+/// Which ghost text a suggestion occupies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Slot {
+    Inline,
+    Sentence,
+}
+
+/// A suggestion on screen, waiting to be accepted or replaced
+#[derive(Debug, Clone)]
+struct Shown {
+    key: BucketKey,
+    text: String,
+    /// Text before the cursor when it appeared, to spot the user typing it out
+    context: String,
+    since: Instant,
+}
+
+impl Shown {
+    /// Ignored, unless the user typed at least the first word of it by hand
+    fn outcome_on_replace(&self, context: &str) -> Outcome {
+        let typed = context.strip_prefix(self.context.as_str()).unwrap_or_default().trim_start();
+        let expected = self.text.trim_start();
+        let first_word = expected.split_whitespace().next().unwrap_or_default();
+        if !first_word.is_empty() && typed.len() >= first_word.len() && expected.starts_with(typed) {
+            Outcome::Partial
+        } else {
+            Outcome::Ignored
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredBucket {
+    #[serde(flatten)]
+    key: BucketKey,
+    #[serde(flatten)]
+    stats: BucketStats,
+}
+
+/// Records what becomes of each suggestion and turns acceptance rates into
+/// calibrated confidences. Stats persist in the config directory.
+pub struct SuggestionAnalytics {
+    buckets: HashMap<BucketKey, BucketStats>,
+    inline: Option<Shown>,
+    sentence: Option<Shown>,
+    path: Option<PathBuf>,
+    dirty: bool,
+}
+
+impl SuggestionAnalytics {
+    pub fn new() -> Self {
+        let path = config_dir().map(|dir| dir.join(STATS_FILE));
+        let stored: Vec<StoredBucket> = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            buckets: stored.into_iter().map(|b| (b.key, b.stats)).collect(),
+            inline: None,
+            sentence: None,
+            path,
+            dirty: false,
+        }
+    }
+
+    fn slot(&mut self, slot: Slot) -> &mut Option<Shown> {
+        match slot {
+            Slot::Inline => &mut self.inline,
+            Slot::Sentence => &mut self.sentence,
+        }
+    }
+
+    /// Note what a slot currently shows. A different suggestion replacing the
+    /// tracked one resolves the old one as ignored or partially accepted.
+    pub fn observe(&mut self, slot: Slot, suggestion: Option<&Suggestion>, provider: AiProvider, context: &str) {
+        let unchanged = match (&*self.slot(slot), suggestion) {
+            (Some(shown), Some(s)) => shown.text == s.text,
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+
+        if let Some(shown) = self.slot(slot).take() {
+            if shown.since.elapsed() >= MIN_VISIBLE {
+                let outcome = shown.outcome_on_replace(context);
+                self.record(shown.key, outcome);
+            }
+        }
+
+        *self.slot(slot) = suggestion.map(|s| {
+            let provider = match s.source {
+                SuggestionSource::Local => AiProvider::Local,
+                SuggestionSource::Api => provider,
+            };
+            Shown {
+                key: BucketKey::new(s.source, provider, s.confidence),
+                text: s.text.clone(),
+                context: context.to_string(),
+                since: Instant::now(),
+            }
+        });
+    }
+
+    /// The suggestion in `slot` was taken, fully or in part
+    pub fn accept(&mut self, slot: Slot, outcome: Outcome) {
+        if let Some(shown) = self.slot(slot).take() {
+            self.record(shown.key, outcome);
+        }
+    }
+
+    fn record(&mut self, key: BucketKey, outcome: Outcome) {
+        let stats = self.buckets.entry(key).or_default();
+        stats.shown += 1;
+        match outcome {
+            Outcome::Accepted => stats.accepted += 1,
+            Outcome::Partial => stats.partial += 1,
+            Outcome::Ignored => stats.ignored += 1,
+        }
+        self.dirty = true;
+    }
+
+    /// The built-in confidence adjusted toward the observed acceptance rate
+    /// of its bucket. With no data it is returned unchanged.
+    pub fn calibrate(&self, source: SuggestionSource, provider: AiProvider, confidence: f32) -> f32 {
+        let provider = match source {
+            SuggestionSource::Local => AiProvider::Local,
+            SuggestionSource::Api => provider,
+        };
+        let key = BucketKey::new(source, provider, confidence);
+        match self.buckets.get(&key) {
+            Some(stats) => {
+                let used = stats.accepted as f32 + 0.5 * stats.partial as f32;
+                (used + PRIOR_WEIGHT * confidence) / (stats.shown as f32 + PRIOR_WEIGHT)
+            }
+            None => confidence,
+        }
+    }
+
+    /// Every bucket with data, ordered by source, provider and confidence
+    pub fn buckets(&self) -> Vec<(BucketKey, BucketStats)> {
+        let mut buckets: Vec<_> = self.buckets.iter().map(|(k, s)| (*k, *s)).collect();
+        buckets.sort_by_key(|(key, _)| *key);
+        buckets
+    }
+
+    /// Write the stats out if anything changed since the last save
+    pub fn save(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        let Some(path) = &self.path else { return };
+
+        let stored: Vec<StoredBucket> = self
+            .buckets()
+            .into_iter()
+            .map(|(key, stats)| StoredBucket { key, stats })
+            .collect();
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(content) = serde_json::to_string_pretty(&stored) {
+            if fs::write(path, content).is_ok() {
+                self.dirty = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+
+    #[test]
+    fn accepted_suggestions_are_counted_by_bucket_and_kept() {
+        let _dir = TestConfigDir::new();
+        let mut analytics = SuggestionAnalytics::new();
+        let suggestion = Suggestion {
+            text: " the lanterns hummed".to_string(),
+            confidence: 0.85,
+            source: SuggestionSource::Api,
+            cached: false,
+        };
+
+        analytics.observe(Slot::Inline, Some(&suggestion), AiProvider::OpenAI, "The clock");
+        analytics.accept(Slot::Inline, Outcome::Accepted);
+
+        let buckets = analytics.buckets();
+        let (key, stats) = buckets[0];
+        assert_eq!((key.source, key.provider, key.bucket), (SuggestionSource::Api, AiProvider::OpenAI, 8));
+        assert_eq!((stats.shown, stats.accepted), (1, 1));
+        assert!(analytics.calibrate(SuggestionSource::Api, AiProvider::OpenAI, 0.85) > 0.85);
+        // Other buckets are left alone
+        assert_eq!(analytics.calibrate(SuggestionSource::Local, AiProvider::OpenAI, 0.85), 0.85);
+
+        analytics.save();
+        assert_eq!(SuggestionAnalytics::new().buckets(), buckets);
+    }
+}
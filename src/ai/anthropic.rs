@@ -1,8 +1,12 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
 
-use crate::config::Config;
-use super::{Suggestion, SuggestionSource};
+use crate::config::{AiProvider, Config};
+use super::chat::{self, ChatMessage};
+use super::critique::CRITIQUE_SYSTEM_PROMPT;
+use super::summary::SUMMARY_SYSTEM_PROMPT;
+use super::{AuditLog, Completion, Suggestion, SuggestionSource, TokenUsage};
 
 #[derive(Serialize)]
 struct Message {
@@ -16,6 +20,8 @@ struct AnthropicRequest {
     max_tokens: u32,
     messages: Vec<Message>,
     system: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
 }
 
 #[derive(Deserialize)]
@@ -23,140 +29,256 @@ struct ContentBlock {
     text: String,
 }
 
+#[derive(Deserialize)]
+struct Usage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
 #[derive(Deserialize)]
 struct AnthropicResponse {
     content: Vec<ContentBlock>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+impl AnthropicResponse {
+    fn token_usage(&self) -> TokenUsage {
+        self.usage
+            .as_ref()
+            .map(|u| TokenUsage {
+                input_tokens: u.input_tokens,
+                output_tokens: u.output_tokens,
+            })
+            .unwrap_or_default()
+    }
 }
 
 pub struct AnthropicClient {
     client: Client,
     api_key: String,
     model: String,
+    base_url: String,
+    offline: bool,
+    /// Per-model overrides from the config's model list
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    audit: AuditLog,
+    style: Option<String>,
 }
 
 impl AnthropicClient {
     pub fn new(config: Config) -> Self {
-        Self {
-            client: Client::new(),
-            api_key: config.anthropic_api_key.unwrap_or_default(),
-            model: "claude-3-haiku-20240307".to_string(), // Fast and cheap
-        }
+        let model = config.anthropic_model.clone();
+        Self::with_model(config, &model)
     }
 
     pub fn with_model(config: Config, model: &str) -> Self {
+        let spec = config.anthropic_models.iter().find(|m| m.id == model);
         Self {
             client: Client::new(),
+            temperature: spec.and_then(|m| m.temperature),
+            max_tokens: spec.and_then(|m| m.max_tokens),
             api_key: config.anthropic_api_key.unwrap_or_default(),
             model: model.to_string(),
+            base_url: config.anthropic_base_url,
+            offline: config.offline_lock,
+            audit: AuditLog::disabled(),
+            style: None,
         }
     }
 
-    pub async fn suggest(&self, context: &str) -> Option<Suggestion> {
-        if self.api_key.is_empty() {
-            return None;
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Describe the author's style in suggestion prompts
+    pub fn with_style(mut self, style: Option<String>) -> Self {
+        self.style = style;
+        self
+    }
+
+    fn styled(&self, system_prompt: &str) -> String {
+        match &self.style {
+            Some(style) => format!("{} {}", system_prompt, style),
+            None => system_prompt.to_string(),
         }
+    }
 
+    pub async fn suggest(&self, context: &str) -> Option<(Suggestion, TokenUsage)> {
         let system_prompt = "You are a creative writing assistant. Given the text context, suggest the next 1-5 words that would naturally continue the writing. Only output the suggested words, nothing else. No quotes, no explanations.";
 
-        let request = AnthropicRequest {
-            model: self.model.clone(),
-            max_tokens: 50,
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: format!("Continue this text with the next few words:\n\n{}", context),
-            }],
-            system: system_prompt.to_string(),
-        };
+        let completion = self.complete(
+            &self.styled(system_prompt),
+            format!("Continue this text with the next few words:\n\n{}", context),
+            50,
+        ).await?;
 
-        let response = self.client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .ok()?;
+        // Ensure suggestion starts with space if context doesn't end with whitespace
+        let final_text = if !context.ends_with(char::is_whitespace) && !completion.text.starts_with(char::is_whitespace) {
+            format!(" {}", completion.text)
+        } else {
+            completion.text
+        };
 
-        if !response.status().is_success() {
-            return None;
-        }
+        Some((Suggestion {
+            text: final_text,
+            confidence: 0.85,
+            source: SuggestionSource::Api,
+            cached: false,
+        }, completion.usage))
+    }
 
-        let data: AnthropicResponse = response.json().await.ok()?;
+    pub async fn suggest_sentence(&self, context: &str) -> Option<(Suggestion, TokenUsage)> {
+        let system_prompt = "You are a creative writing assistant. Given the text context, suggest a complete sentence or phrase (10-20 words) that would naturally continue the writing. Only output the suggested text, nothing else. No quotes, no explanations.";
 
-        let suggestion_text = data.content.first()?.text.trim().to_string();
+        let completion = self.complete(
+            &self.styled(system_prompt),
+            format!("Continue this text with a natural sentence:\n\n{}", context),
+            100,
+        ).await?;
 
-        if suggestion_text.is_empty() {
-            return None;
-        }
-
-        // Ensure suggestion starts with space if context doesn't end with whitespace
-        let final_text = if !context.ends_with(char::is_whitespace) && !suggestion_text.starts_with(char::is_whitespace) {
-            format!(" {}", suggestion_text)
+        let final_text = if !context.ends_with(char::is_whitespace) && !completion.text.starts_with(char::is_whitespace) {
+            format!(" {}", completion.text)
         } else {
-            suggestion_text
+            completion.text
         };
 
-        Some(Suggestion {
+        Some((Suggestion {
             text: final_text,
-            confidence: 0.85,
+            confidence: 0.90,
             source: SuggestionSource::Api,
-        })
+            cached: false,
+        }, completion.usage))
     }
 
-    pub async fn suggest_sentence(&self, context: &str) -> Option<Suggestion> {
-        if self.api_key.is_empty() {
+    pub fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    /// Continue a conversation about the document, returning the reply
+    pub async fn chat(&self, system: &str, messages: &[ChatMessage]) -> Option<Completion> {
+        let messages = messages
+            .iter()
+            .map(|m| Message {
+                role: m.role.as_str().to_string(),
+                content: m.content.clone(),
+            })
+            .collect();
+        self.complete_messages(system, messages, 600).await
+    }
+
+    /// Ask for JSON critique annotations on a passage
+    pub async fn critique(&self, text: &str) -> Option<Completion> {
+        self.complete(CRITIQUE_SYSTEM_PROMPT, text.to_string(), 800).await
+    }
+
+    /// Summarize one chapter for the long-range context
+    pub async fn summarize(&self, chapter: &str) -> Option<Completion> {
+        self.complete(SUMMARY_SYSTEM_PROMPT, chapter.to_string(), 300).await
+    }
+
+    /// Send a single-message request, recording it in the audit log
+    async fn complete(&self, system: &str, prompt: String, max_tokens: u32) -> Option<Completion> {
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: prompt,
+        }];
+        self.complete_messages(system, messages, max_tokens).await
+    }
+
+    async fn complete_messages(&self, system: &str, messages: Vec<Message>, max_tokens: u32) -> Option<Completion> {
+        if self.api_key.is_empty() || self.offline {
             return None;
         }
 
-        let system_prompt = "You are a creative writing assistant. Given the text context, suggest a complete sentence or phrase (10-20 words) that would naturally continue the writing. Only output the suggested text, nothing else. No quotes, no explanations.";
-
+        let prompt = chat::transcript(messages.iter().map(|m| (m.role.as_str(), m.content.as_str())));
         let request = AnthropicRequest {
             model: self.model.clone(),
-            max_tokens: 100,
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: format!("Continue this text with a natural sentence:\n\n{}", context),
-            }],
-            system: system_prompt.to_string(),
+            max_tokens: self.max_tokens.map_or(max_tokens, |cap| cap.min(max_tokens)),
+            messages,
+            system: system.to_string(),
+            temperature: self.temperature,
         };
 
+        let started = Instant::now();
+        let result = self.send(&request).await;
+        self.audit.record(
+            AiProvider::Anthropic,
+            &request.model,
+            Some(&request.system),
+            &prompt,
+            &result,
+            started.elapsed(),
+        );
+        result.ok()
+    }
+
+    async fn send(&self, request: &AnthropicRequest) -> Result<Completion, String> {
         let response = self.client
-            .post("https://api.anthropic.com/v1/messages")
+            .post(format!("{}/messages", self.base_url.trim_end_matches('/')))
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&request)
+            .json(request)
             .send()
             .await
-            .ok()?;
+            .map_err(|e| e.to_string())?;
 
         if !response.status().is_success() {
-            return None;
+            return Err(format!("HTTP {}", response.status()));
         }
 
-        let data: AnthropicResponse = response.json().await.ok()?;
+        let data: AnthropicResponse = response.json().await.map_err(|e| e.to_string())?;
 
-        let suggestion_text = data.content.first()?.text.trim().to_string();
+        let text = data.content
+            .first()
+            .map(|block| block.text.trim().to_string())
+            .unwrap_or_default();
 
-        if suggestion_text.is_empty() {
-            return None;
+        if text.is_empty() {
+            return Err("empty response".to_string());
         }
 
-        let final_text = if !context.ends_with(char::is_whitespace) && !suggestion_text.starts_with(char::is_whitespace) {
-            format!(" {}", suggestion_text)
-        } else {
-            suggestion_text
-        };
-
-        Some(Suggestion {
-            text: final_text,
-            confidence: 0.90,
-            source: SuggestionSource::Api,
+        Ok(Completion {
+            text,
+            usage: data.token_usage(),
         })
     }
+}
 
-    pub fn model_name(&self) -> &str {
-        &self.model
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::MockServer;
+
+    #[tokio::test]
+    async fn selected_model_and_its_params_are_sent() {
+        let server = MockServer::start().await.unwrap();
+        let mut config = Config {
+            anthropic_api_key: Some("test-anthropic-key".to_string()),
+            ai_provider: AiProvider::Anthropic,
+            ..Config::default()
+        };
+        server.configure(&mut config);
+        config.cycle_model();
+        let model = config.anthropic_model.clone();
+        let spec = config.anthropic_models.iter_mut().find(|m| m.id == model).unwrap();
+        spec.temperature = Some(0.3);
+        spec.max_tokens = Some(12);
+
+        AnthropicClient::new(config.clone()).suggest("The clock tower").await.unwrap();
+
+        let body = &server.requests()[0].1;
+        assert_eq!(body["model"], model.as_str());
+        assert_eq!(body["max_tokens"], 12);
+        assert!((body["temperature"].as_f64().unwrap() - 0.3).abs() < 1e-6);
+
+        // A large cap leaves each request's own budget alone
+        let spec = config.anthropic_models.iter_mut().find(|m| m.id == model).unwrap();
+        spec.max_tokens = Some(4096);
+        AnthropicClient::new(config).summarize("The clock struck thirteen.").await.unwrap();
+        assert_eq!(server.requests()[1].1["max_tokens"], 300);
     }
 }
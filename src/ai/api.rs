@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
-use super::{Suggestion, SuggestionSource};
-use crate::config::Config;
+use std::time::Instant;
+use super::chat::{self, ChatMessage};
+use super::critique::CRITIQUE_SYSTEM_PROMPT;
+use super::summary::SUMMARY_SYSTEM_PROMPT;
+use super::{AuditLog, Completion, Suggestion, SuggestionSource, TokenUsage};
+use crate::config::{AiProvider, Config};
 
 #[derive(Debug, Serialize)]
 struct ChatRequest {
@@ -19,6 +23,26 @@ struct Message {
 #[derive(Debug, Deserialize)]
 struct ChatResponse {
     choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Usage {
+    prompt_tokens: u64,
+    completion_tokens: u64,
+}
+
+impl ChatResponse {
+    fn token_usage(&self) -> TokenUsage {
+        self.usage
+            .as_ref()
+            .map(|u| TokenUsage {
+                input_tokens: u.prompt_tokens,
+                output_tokens: u.completion_tokens,
+            })
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,6 +53,8 @@ struct Choice {
 pub struct OpenAIClient {
     client: reqwest::Client,
     config: Config,
+    audit: AuditLog,
+    style: Option<String>,
 }
 
 impl OpenAIClient {
@@ -36,100 +62,209 @@ impl OpenAIClient {
         Self {
             client: reqwest::Client::new(),
             config,
+            audit: AuditLog::disabled(),
+            style: None,
         }
     }
 
-    pub async fn suggest(&self, context: &str) -> Option<Suggestion> {
-        let api_key = self.config.openai_api_key.as_ref()?;
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = audit;
+        self
+    }
 
-        if api_key.is_empty() {
-            return None;
-        }
+    /// Describe the author's style in suggestion prompts
+    pub fn with_style(mut self, style: Option<String>) -> Self {
+        self.style = style;
+        self
+    }
+
+    fn style_line(&self) -> String {
+        self.style.as_ref().map(|s| format!("{}\n", s)).unwrap_or_default()
+    }
 
+    /// The default voice, unless the author's own style replaces it
+    fn persona<'a>(&self, persona: &'a str) -> &'a str {
+        if self.style.is_some() { "" } else { persona }
+    }
+
+    pub async fn suggest(&self, context: &str) -> Option<(Suggestion, TokenUsage)> {
         let prompt = format!(
             "You are a creative writing assistant. Continue this text with 3-8 words. \
-             Be creative, unexpected, and slightly surreal. Only output the continuation, nothing else.\n\n\
+             {}Only output the continuation, nothing else.\n{}\n\
              Text: {}",
+            self.persona("Be creative, unexpected, and slightly surreal. "),
+            self.style_line(),
             context
         );
 
-        let request = ChatRequest {
-            model: self.config.openai_model.clone(),
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: prompt,
-            }],
-            max_tokens: 30,
-            temperature: 0.9,
-        };
+        let completion = self.complete(prompt, 30, 0.9).await?;
 
-        let response = self.client
-            .post("https://api.openai.com/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .ok()?;
+        Some((Suggestion {
+            text: format!(" {}", completion.text),
+            confidence: 0.85,
+            source: SuggestionSource::Api,
+            cached: false,
+        }, completion.usage))
+    }
 
-        let chat_response: ChatResponse = response.json().await.ok()?;
-        let text = chat_response.choices.first()?.message.content.trim().to_string();
+    pub async fn suggest_sentence(&self, context: &str) -> Option<(Suggestion, TokenUsage)> {
+        let prompt = format!(
+            "You are an experimental creative writing assistant. Complete this partial text with one full sentence. \
+             {}Only output the sentence continuation, nothing else.\n{}\n\
+             Text: {}",
+            self.persona("Be surreal, dreamlike, and unexpected. "),
+            self.style_line(),
+            context
+        );
 
-        if text.is_empty() {
-            return None;
-        }
+        let completion = self.complete(prompt, 60, 1.0).await?;
 
-        Some(Suggestion {
-            text: format!(" {}", text),
-            confidence: 0.85,
+        Some((Suggestion {
+            text: completion.text,
+            confidence: 0.9,
             source: SuggestionSource::Api,
-        })
+            cached: false,
+        }, completion.usage))
     }
 
-    pub async fn suggest_sentence(&self, context: &str) -> Option<Suggestion> {
+    /// Continue a conversation about the document, returning the reply
+    pub async fn chat(&self, system: &str, messages: &[ChatMessage]) -> Option<Completion> {
+        let messages = messages
+            .iter()
+            .map(|m| Message {
+                role: m.role.as_str().to_string(),
+                content: m.content.clone(),
+            })
+            .collect();
+        self.complete_messages(Some(system), messages, 600, 0.7).await
+    }
+
+    /// Ask for JSON critique annotations on a passage
+    pub async fn critique(&self, text: &str) -> Option<Completion> {
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: text.to_string(),
+        }];
+        self.complete_messages(Some(CRITIQUE_SYSTEM_PROMPT), messages, 800, 0.3).await
+    }
+
+    /// Summarize one chapter for the long-range context
+    pub async fn summarize(&self, chapter: &str) -> Option<Completion> {
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: chapter.to_string(),
+        }];
+        self.complete_messages(Some(SUMMARY_SYSTEM_PROMPT), messages, 300, 0.3).await
+    }
+
+    /// Send a single-message chat request, recording it in the audit log
+    async fn complete(&self, prompt: String, max_tokens: u32, temperature: f32) -> Option<Completion> {
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: prompt,
+        }];
+        self.complete_messages(None, messages, max_tokens, temperature).await
+    }
+
+    async fn complete_messages(
+        &self,
+        system: Option<&str>,
+        messages: Vec<Message>,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Option<Completion> {
         let api_key = self.config.openai_api_key.as_ref()?;
 
-        if api_key.is_empty() {
+        if api_key.is_empty() || self.config.offline_lock {
             return None;
         }
 
-        let prompt = format!(
-            "You are an experimental creative writing assistant. Complete this partial text with one full sentence. \
-             Be surreal, dreamlike, and unexpected. Only output the sentence continuation, nothing else.\n\n\
-             Text: {}",
-            context
-        );
+        let prompt = chat::transcript(messages.iter().map(|m| (m.role.as_str(), m.content.as_str())));
+        let system_message = system.map(|content| Message {
+            role: "system".to_string(),
+            content: content.to_string(),
+        });
 
+        let spec = self.config.model_spec(AiProvider::OpenAI);
         let request = ChatRequest {
             model: self.config.openai_model.clone(),
-            messages: vec![Message {
-                role: "user".to_string(),
-                content: prompt,
-            }],
-            max_tokens: 60,
-            temperature: 1.0,
+            messages: system_message.into_iter().chain(messages).collect(),
+            max_tokens: spec.and_then(|m| m.max_tokens).map_or(max_tokens, |cap| cap.min(max_tokens)),
+            temperature: spec.and_then(|m| m.temperature).unwrap_or(temperature),
         };
 
+        let started = Instant::now();
+        let result = self.send(api_key, &request).await;
+        self.audit.record(
+            AiProvider::OpenAI,
+            &request.model,
+            system,
+            &prompt,
+            &result,
+            started.elapsed(),
+        );
+        result.ok()
+    }
+
+    async fn send(&self, api_key: &str, request: &ChatRequest) -> Result<Completion, String> {
         let response = self.client
-            .post("https://api.openai.com/v1/chat/completions")
+            .post(format!("{}/chat/completions", self.config.openai_base_url.trim_end_matches('/')))
             .header("Authorization", format!("Bearer {}", api_key))
             .header("Content-Type", "application/json")
-            .json(&request)
+            .json(request)
             .send()
             .await
-            .ok()?;
+            .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
 
-        let chat_response: ChatResponse = response.json().await.ok()?;
-        let text = chat_response.choices.first()?.message.content.trim().to_string();
+        let chat_response: ChatResponse = response.json().await.map_err(|e| e.to_string())?;
+        let text = chat_response.choices
+            .first()
+            .map(|choice| choice.message.content.trim().to_string())
+            .unwrap_or_default();
 
         if text.is_empty() {
-            return None;
+            return Err("empty response".to_string());
         }
 
-        Some(Suggestion {
+        Ok(Completion {
             text,
-            confidence: 0.9,
-            source: SuggestionSource::Api,
+            usage: chat_response.token_usage(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::MockServer;
+
+    #[tokio::test]
+    async fn a_style_profile_replaces_the_surreal_persona() {
+        let server = MockServer::start().await.unwrap();
+        let mut config = Config {
+            openai_api_key: Some("test-openai-key".to_string()),
+            ..Config::default()
+        };
+        server.configure(&mut config);
+
+        OpenAIClient::new(config.clone()).suggest("The clock tower").await.unwrap();
+        OpenAIClient::new(config)
+            .with_style(Some("Write in the past tense.".to_string()))
+            .suggest_sentence("The clock tower")
+            .await
+            .unwrap();
+
+        let prompts: Vec<String> = server
+            .requests()
+            .iter()
+            .map(|(_, body)| body["messages"][0]["content"].as_str().unwrap().to_string())
+            .collect();
+        assert!(prompts[0].contains("slightly surreal"));
+        assert!(!prompts[1].contains("surreal"));
+        assert!(prompts[1].contains("Write in the past tense."));
+    }
+}
@@ -0,0 +1,239 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::usage::document_key;
+use super::{Completion, TokenUsage};
+use crate::config::{config_dir, AiProvider, Config};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+fn audit_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(AUDIT_FILE))
+}
+
+/// One outbound request exactly as it was sent, with what came back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub document: Option<String>,
+    pub provider: AiProvider,
+    pub model: String,
+    pub system: Option<String>,
+    pub prompt: String,
+    pub response: Option<String>,
+    pub usage: Option<TokenUsage>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Handle the AI clients use to append to the opt-in JSONL audit log.
+/// A disabled handle records nothing.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    document: Option<String>,
+}
+
+impl AuditLog {
+    pub fn new(config: &Config, document: Option<&Path>) -> Self {
+        Self {
+            path: if config.audit_log { audit_path() } else { None },
+            document: document.map(document_key),
+        }
+    }
+
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn record(
+        &self,
+        provider: AiProvider,
+        model: &str,
+        system: Option<&str>,
+        prompt: &str,
+        result: &Result<Completion, String>,
+        latency: Duration,
+    ) {
+        let Some(path) = &self.path else { return };
+
+        let record = AuditRecord {
+            timestamp: Utc::now(),
+            document: self.document.clone(),
+            provider,
+            model: model.to_string(),
+            system: system.map(String::from),
+            prompt: prompt.to_string(),
+            response: result.as_ref().ok().map(|c| c.text.clone()),
+            usage: result.as_ref().ok().map(|c| c.usage),
+            latency_ms: latency.as_millis() as u64,
+            error: result.as_ref().err().cloned(),
+        };
+
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let (Ok(mut file), Ok(line)) = (
+            OpenOptions::new().create(true).append(true).open(path),
+            serde_json::to_string(&record),
+        ) {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    /// Every record in the log, oldest first
+    pub fn read_all() -> Vec<AuditRecord> {
+        audit_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// State for the audit log viewer popup
+#[derive(Debug, Clone, Default)]
+pub struct AuditViewer {
+    pub is_active: bool,
+    records: Vec<AuditRecord>,
+    /// Documents seen in the log; `None` stands for unsaved buffers
+    documents: Vec<Option<String>>,
+    /// 0 shows every document, otherwise an index into `documents` plus one
+    filter: usize,
+    pub selected: usize,
+}
+
+impl AuditViewer {
+    pub fn open(&mut self, current_document: Option<&Path>) {
+        self.records = AuditLog::read_all();
+        self.records.reverse();
+
+        self.documents.clear();
+        for record in &self.records {
+            if !self.documents.contains(&record.document) {
+                self.documents.push(record.document.clone());
+            }
+        }
+
+        // Start on the open document when it has any entries
+        let current = current_document.map(document_key);
+        self.filter = self.documents
+            .iter()
+            .position(|d| current.is_some() && *d == current)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        self.selected = 0;
+        self.is_active = true;
+    }
+
+    pub fn close(&mut self) {
+        self.is_active = false;
+        self.records.clear();
+    }
+
+    pub fn filter_label(&self) -> String {
+        match self.filter {
+            0 => "all documents".to_string(),
+            n => match &self.documents[n - 1] {
+                Some(doc) => Path::new(doc)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| doc.clone()),
+                None => "unsaved".to_string(),
+            },
+        }
+    }
+
+    pub fn visible(&self) -> Vec<&AuditRecord> {
+        match self.filter {
+            0 => self.records.iter().collect(),
+            n => {
+                let document = &self.documents[n - 1];
+                self.records.iter().filter(|r| &r.document == document).collect()
+            }
+        }
+    }
+
+    pub fn next_filter(&mut self) {
+        self.filter = (self.filter + 1) % (self.documents.len() + 1);
+        self.selected = 0;
+    }
+
+    pub fn prev_filter(&mut self) {
+        let count = self.documents.len() + 1;
+        self.filter = (self.filter + count - 1) % count;
+        self.selected = 0;
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.selected + 1 < self.visible().len() {
+            self.selected += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+
+    #[test]
+    fn requests_are_logged_only_when_opted_in() {
+        let dir = TestConfigDir::new();
+        let document = dir.path().join("novel.md");
+        let sent = Completion {
+            text: "the bells".to_string(),
+            usage: TokenUsage { input_tokens: 9, output_tokens: 2 },
+        };
+        let result = Ok(sent);
+
+        AuditLog::new(&Config::default(), None)
+            .record(AiProvider::OpenAI, "gpt-4o", None, "ignored", &result, Duration::from_millis(5));
+        assert!(!dir.path().join(AUDIT_FILE).exists());
+
+        let config = Config { audit_log: true, ..Config::default() };
+        let log = AuditLog::new(&config, Some(&document));
+        log.record(AiProvider::OpenAI, "gpt-4o", Some("Be brief."), "The clock", &result, Duration::from_millis(120));
+        AuditLog::new(&config, None).record(
+            AiProvider::Anthropic,
+            "claude",
+            None,
+            "The tower",
+            &Err("HTTP 429".to_string()),
+            Duration::from_millis(30),
+        );
+
+        let lines = fs::read_to_string(dir.path().join(AUDIT_FILE)).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        let records = AuditLog::read_all();
+        let (first, second) = (&records[0], &records[1]);
+        assert_eq!((first.system.as_deref(), first.prompt.as_str()), (Some("Be brief."), "The clock"));
+        assert_eq!((first.response.as_deref(), first.latency_ms), (Some("the bells"), 120));
+        assert_eq!(first.usage.map(|u| u.total()), Some(11));
+        assert_eq!(first.document, Some(document_key(&document)));
+        assert_eq!((second.provider, second.error.as_deref()), (AiProvider::Anthropic, Some("HTTP 429")));
+        assert!(second.response.is_none() && second.document.is_none());
+
+        let mut viewer = AuditViewer::default();
+        viewer.open(Some(&document));
+        assert_eq!(viewer.filter_label(), "novel.md");
+        assert_eq!(viewer.visible().len(), 1);
+        viewer.next_filter();
+        assert_eq!(viewer.filter_label(), "all documents");
+        assert_eq!(viewer.visible().len(), 2);
+        viewer.next_filter();
+        assert_eq!(viewer.filter_label(), "unsaved");
+    }
+}
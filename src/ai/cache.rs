@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+
+use super::{stable_hash, Suggestion, SuggestionSource};
+use crate::config::{config_dir, AiProvider, Config};
+
+const CACHE_FILE: &str = "completion_cache.json";
+
+/// Identifies a completion request: the same provider, model and prompt
+/// template over the same (normalized) context yields the same key. The
+/// whole request is kept alongside its hash, so a hash collision is a miss
+/// rather than another prompt's completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    hash: u64,
+    request: String,
+}
+
+impl CacheKey {
+    pub fn new(provider: AiProvider, model: &str, template: &str, context: &str) -> Self {
+        let request = format!("{}\n{}\n{}\n{}", provider, model, template, normalize_context(context));
+        Self { hash: stable_hash(&request), request }
+    }
+}
+
+/// Collapse runs of whitespace so that re-wrapped or re-indented passages
+/// still hit the cache
+fn normalize_context(context: &str) -> String {
+    context.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    key: u64,
+    request: String,
+    text: String,
+    confidence: f32,
+}
+
+/// LRU cache of API completions, optionally persisted to the config directory
+pub struct CompletionCache {
+    entries: HashMap<u64, CacheEntry>,
+    order: VecDeque<u64>,
+    capacity: usize,
+    enabled: bool,
+    path: Option<PathBuf>,
+}
+
+impl CompletionCache {
+    pub fn new(config: &Config) -> Self {
+        let path = if config.cache_on_disk {
+            config_dir().map(|dir| dir.join(CACHE_FILE))
+        } else {
+            None
+        };
+
+        let mut cache = Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            capacity: config.cache_capacity.max(1),
+            enabled: config.cache_enabled,
+            path,
+        };
+        cache.load();
+        cache
+    }
+
+    pub fn get(&mut self, key: &CacheKey) -> Option<Suggestion> {
+        if !self.enabled {
+            return None;
+        }
+
+        let entry = self.entries.get(&key.hash).filter(|entry| entry.request == key.request)?;
+        let suggestion = Suggestion {
+            text: entry.text.clone(),
+            confidence: entry.confidence,
+            source: SuggestionSource::Api,
+            cached: true,
+        };
+        self.touch(key.hash);
+        Some(suggestion)
+    }
+
+    pub fn insert(&mut self, key: CacheKey, suggestion: &Suggestion) {
+        if !self.enabled {
+            return;
+        }
+
+        self.entries.insert(key.hash, CacheEntry {
+            key: key.hash,
+            request: key.request,
+            text: suggestion.text.clone(),
+            confidence: suggestion.confidence,
+        });
+        self.touch(key.hash);
+
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.save();
+    }
+
+    fn touch(&mut self, key: u64) {
+        self.order.retain(|k| *k != key);
+        self.order.push_back(key);
+    }
+
+    fn load(&mut self) {
+        let Some(path) = &self.path else { return };
+        let Ok(content) = fs::read_to_string(path) else { return };
+        let Ok(entries) = serde_json::from_str::<Vec<CacheEntry>>(&content) else { return };
+
+        // Entries are stored least recently used first
+        for entry in entries.into_iter().rev().take(self.capacity).rev() {
+            self.order.push_back(entry.key);
+            self.entries.insert(entry.key, entry);
+        }
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else { return };
+        let entries: Vec<&CacheEntry> = self.order
+            .iter()
+            .filter_map(|key| self.entries.get(key))
+            .collect();
+
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Ok(json) = serde_json::to_string(&entries) {
+            let _ = fs::write(path, json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+
+    fn suggestion(text: &str) -> Suggestion {
+        Suggestion { text: text.to_string(), confidence: 0.85, source: SuggestionSource::Api, cached: false }
+    }
+
+    fn key(context: &str) -> CacheKey {
+        CacheKey::new(AiProvider::OpenAI, "gpt-4o-mini", "word", context)
+    }
+
+    #[test]
+    fn hits_misses_and_evicts_the_least_recent() {
+        let config = Config { cache_enabled: true, cache_capacity: 2, cache_on_disk: false, ..Config::default() };
+        let mut cache = CompletionCache::new(&config);
+        assert!(cache.get(&key("The clock")).is_none());
+
+        cache.insert(key("The clock"), &suggestion(" struck"));
+        let hit = cache.get(&key("The   clock\n")).expect("whitespace is normalized");
+        assert!(hit.cached && hit.text == " struck");
+        assert!(cache.get(&CacheKey::new(AiProvider::Anthropic, "gpt-4o-mini", "word", "The clock")).is_none());
+        assert!(cache.get(&CacheKey::new(AiProvider::OpenAI, "gpt-4o", "word", "The clock")).is_none());
+
+        cache.insert(key("The tower"), &suggestion(" leaned"));
+        cache.get(&key("The clock"));
+        cache.insert(key("The ferry"), &suggestion(" left"));
+        assert!(cache.get(&key("The tower")).is_none());
+        assert!(cache.get(&key("The clock")).is_some());
+    }
+
+    #[test]
+    fn colliding_hashes_are_misses() {
+        let mut cache = CompletionCache::new(&Config { cache_enabled: true, cache_on_disk: false, ..Config::default() });
+        let original = key("The clock");
+        cache.insert(original.clone(), &suggestion(" struck"));
+
+        let collision = CacheKey { hash: original.hash, request: "something else".to_string() };
+        assert!(cache.get(&collision).is_none());
+        assert!(cache.get(&original).is_some());
+    }
+
+    #[test]
+    fn entries_persist_with_stable_keys() {
+        let dir = TestConfigDir::new();
+        let config = Config { cache_enabled: true, cache_on_disk: true, ..Config::default() };
+        CompletionCache::new(&config).insert(key("The clock"), &suggestion(" struck"));
+        assert!(dir.path().join(CACHE_FILE).exists());
+
+        assert_eq!(key("The clock").hash, stable_hash("OpenAI\ngpt-4o-mini\nword\nThe clock"));
+        let mut reloaded = CompletionCache::new(&config);
+        assert_eq!(reloaded.get(&key("The clock")).unwrap().text, " struck");
+        assert!(CompletionCache::new(&Config { cache_enabled: false, ..config }).get(&key("The clock")).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+/// Standing instructions for the chat pane; the passage under discussion is
+/// appended on every turn so it always reflects the current text
+const CHAT_SYSTEM_PROMPT: &str = "You are a perceptive editor talking with an author about their manuscript. \
+Answer their questions about the passage below candidly and concisely. \
+Do not rewrite the passage unless asked to.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+/// System prompt for one chat turn, carrying the selection or chapter the
+/// conversation is about
+pub fn chat_system_prompt(context_label: &str, context: &str) -> String {
+    format!("{}\n\n{}:\n{}", CHAT_SYSTEM_PROMPT, context_label, context)
+}
+
+/// Flatten a conversation into the single prompt string the audit log keeps
+pub fn transcript<'a>(turns: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    let turns: Vec<(&str, &str)> = turns.into_iter().collect();
+    match turns.as_slice() {
+        [(_, content)] => content.to_string(),
+        _ => turns
+            .iter()
+            .map(|(role, content)| format!("{}: {}", role, content))
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    }
+}
+
+/// State for the chat side panel
+#[derive(Debug, Clone, Default)]
+pub struct ChatPanel {
+    pub is_active: bool,
+    /// Keys go to the chat input rather than the editor
+    pub focused: bool,
+    pub messages: Vec<ChatMessage>,
+    pub input: String,
+    pub loading: bool,
+    /// Lines scrolled up from the newest message
+    pub scroll: usize,
+}
+
+impl ChatPanel {
+    /// Open and focus the panel, focus it if it's open in the background,
+    /// or close it if it already has focus
+    pub fn toggle(&mut self) {
+        if !self.is_active {
+            self.is_active = true;
+            self.focused = true;
+        } else if !self.focused {
+            self.focused = true;
+        } else {
+            self.is_active = false;
+            self.focused = false;
+        }
+    }
+
+    pub fn unfocus(&mut self) {
+        self.focused = false;
+    }
+
+    pub fn add_char(&mut self, c: char) {
+        self.input.push(c);
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+
+    /// Take the typed question, if there is one
+    pub fn take_input(&mut self) -> Option<String> {
+        let input = self.input.trim().to_string();
+        if input.is_empty() {
+            return None;
+        }
+        self.input.clear();
+        Some(input)
+    }
+
+    pub fn push(&mut self, role: ChatRole, content: String) {
+        self.messages.push(ChatMessage { role, content });
+        self.scroll = 0;
+    }
+
+    /// The most recent answer, for inserting into the document
+    pub fn last_reply(&self) -> Option<&str> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == ChatRole::Assistant)
+            .map(|m| m.content.as_str())
+    }
+
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.scroll = 0;
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll += 1;
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+}
@@ -0,0 +1,236 @@
+use serde::Deserialize;
+
+use super::RedactionMap;
+
+pub const CRITIQUE_SYSTEM_PROMPT: &str = "You are a demanding fiction editor. Point out weak pacing, clichés and unclear sentences in the text you are given. \
+Reply with JSON only, in the form {\"annotations\": [{\"kind\": \"pacing\" | \"cliche\" | \"unclear\", \"start\": <character offset>, \"end\": <character offset>, \"text\": \"<exact excerpt>\", \"note\": \"<one short sentence>\"}]}. \
+Offsets count characters from the start of the text, end exclusive, and \"text\" must be copied exactly from it. \
+Flag only the most important problems, at most ten. Reply with {\"annotations\": []} if there is nothing worth flagging.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CritiqueKind {
+    Pacing,
+    #[serde(alias = "cliché", alias = "cliches")]
+    Cliche,
+    Unclear,
+    #[serde(other)]
+    Other,
+}
+
+impl std::fmt::Display for CritiqueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CritiqueKind::Pacing => write!(f, "Pacing"),
+            CritiqueKind::Cliche => write!(f, "Cliché"),
+            CritiqueKind::Unclear => write!(f, "Unclear"),
+            CritiqueKind::Other => write!(f, "Note"),
+        }
+    }
+}
+
+/// A critique note anchored to a byte range of one buffer line. Ranges that
+/// span lines are split into one annotation per line.
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub kind: CritiqueKind,
+    pub note: String,
+    pub line: usize,
+    pub start: usize,
+    pub end: usize,
+    /// The text the note was written about, so edits can retire it
+    excerpt: String,
+}
+
+impl Annotation {
+    /// Whether the annotated text is still where it was
+    pub fn is_current(&self, lines: &[String]) -> bool {
+        lines
+            .get(self.line)
+            .and_then(|line| line.get(self.start..self.end))
+            .is_some_and(|text| text == self.excerpt)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawAnnotation {
+    #[serde(default = "default_kind")]
+    kind: CritiqueKind,
+    start: Option<usize>,
+    end: Option<usize>,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    note: String,
+}
+
+fn default_kind() -> CritiqueKind {
+    CritiqueKind::Other
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawResponse {
+    Wrapped { annotations: Vec<RawAnnotation> },
+    List(Vec<RawAnnotation>),
+}
+
+/// Parse the provider's JSON into annotations on the buffer, where `text` is
+/// the (unredacted) passage that was analyzed and starts at `first_line`.
+/// Returns `None` when the reply isn't usable JSON.
+pub fn parse_annotations(
+    response: &str,
+    text: &str,
+    first_line: usize,
+    redactions: &RedactionMap,
+) -> Option<Vec<Annotation>> {
+    // Models like to wrap JSON in prose or code fences
+    let start = response.find(['{', '['])?;
+    let end = response.rfind(['}', ']'])?;
+    let raw: RawResponse = serde_json::from_str(response.get(start..=end)?).ok()?;
+    let raw = match raw {
+        RawResponse::Wrapped { annotations } => annotations,
+        RawResponse::List(list) => list,
+    };
+
+    let mut annotations = Vec::new();
+    for item in raw {
+        let excerpt = redactions.restore(&item.text);
+        let Some((start, end)) = locate(text, &excerpt, item.start, item.end, redactions.is_empty()) else {
+            continue;
+        };
+        let note = redactions.restore(item.note.trim());
+        annotations.extend(split_lines(text, first_line, start, end, item.kind, &note));
+    }
+    Some(annotations)
+}
+
+/// Byte range of the annotated text. Character offsets from the model are
+/// trusted only when they agree with the quoted excerpt; otherwise the
+/// excerpt occurrence nearest the offset wins.
+fn locate(text: &str, excerpt: &str, start: Option<usize>, end: Option<usize>, offsets_valid: bool) -> Option<(usize, usize)> {
+    let to_byte = |chars: usize| {
+        text.char_indices().map(|(i, _)| i).chain(std::iter::once(text.len())).nth(chars)
+    };
+    let hinted = match (start, end) {
+        (Some(s), Some(e)) if s < e && offsets_valid => to_byte(s).zip(to_byte(e)),
+        _ => None,
+    };
+
+    if let Some((s, e)) = hinted {
+        if excerpt.is_empty() || text.get(s..e) == Some(excerpt) {
+            return Some((s, e));
+        }
+    }
+    if excerpt.is_empty() {
+        return None;
+    }
+
+    let hint = hinted.map(|(s, _)| s).unwrap_or(0);
+    text.match_indices(excerpt)
+        .map(|(i, _)| i)
+        .min_by_key(|i| i.abs_diff(hint))
+        .map(|i| (i, i + excerpt.len()))
+}
+
+fn split_lines(text: &str, first_line: usize, start: usize, end: usize, kind: CritiqueKind, note: &str) -> Vec<Annotation> {
+    let mut pieces = Vec::new();
+    let mut offset = 0;
+    for (i, line) in text.split('\n').enumerate() {
+        let line_end = offset + line.len();
+        let (s, e) = (start.max(offset), end.min(line_end));
+        if s < e {
+            pieces.push(Annotation {
+                kind,
+                note: note.to_string(),
+                line: first_line + i,
+                start: s - offset,
+                end: e - offset,
+                excerpt: text[s..e].to_string(),
+            });
+        }
+        offset = line_end + 1;
+    }
+    pieces
+}
+
+/// Critique annotations on the current document
+#[derive(Debug, Clone, Default)]
+pub struct Critique {
+    pub annotations: Vec<Annotation>,
+    pub loading: bool,
+}
+
+impl Critique {
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.annotations.clear();
+    }
+
+    /// The still-current annotation covering a byte position, if any
+    pub fn at(&self, lines: &[String], line: usize, col: usize) -> Option<&Annotation> {
+        self.annotations
+            .iter()
+            .find(|a| a.line == line && col >= a.start && col < a.end && a.is_current(lines))
+    }
+
+    /// The still-current annotations on one line
+    pub fn on_line(&self, lines: &[String], line: usize) -> Vec<&Annotation> {
+        self.annotations
+            .iter()
+            .filter(|a| a.line == line && a.is_current(lines))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotations_anchor_to_their_excerpt() {
+        let lines = vec!["The clock tower struck thirteen.".to_string()];
+        let reply = "```json\n{\"annotations\": [{\"kind\": \"cliche\", \"start\": 0, \"end\": 3, \
+                     \"text\": \"struck thirteen\", \"note\": \"Borrowed from Orwell.\"}]}\n```";
+        let critique = Critique {
+            annotations: parse_annotations(reply, &lines[0], 0, &RedactionMap::default()).unwrap(),
+            loading: false,
+        };
+
+        let note = critique.at(&lines, 0, 20).expect("annotation");
+        assert_eq!((note.start, note.end), (16, 31));
+        assert_eq!((note.kind, note.note.as_str()), (CritiqueKind::Cliche, "Borrowed from Orwell."));
+
+        // Edits retire notes whose text moved
+        let edited = vec![format!("At midnight {}", lines[0])];
+        assert!(critique.at(&edited, 0, 20).is_none());
+    }
+
+    #[test]
+    fn multi_line_ranges_split_per_line() {
+        let text = "She waited.\nAnd waited.";
+        let reply = r#"[{"kind": "pacing", "text": "waited.\nAnd", "note": "Drags."}]"#;
+        let annotations = parse_annotations(reply, text, 4, &RedactionMap::default()).unwrap();
+        let ranges: Vec<_> = annotations.iter().map(|a| (a.line, a.start, a.end)).collect();
+        assert_eq!(ranges, [(4, 4, 11), (5, 0, 3)]);
+        assert!(parse_annotations("no JSON here", text, 0, &RedactionMap::default()).is_none());
+    }
+
+    #[test]
+    fn ranges_are_bytes_even_after_wide_characters() {
+        let lines = vec!["Café naïve — struck thirteen.".to_string()];
+        let reply = r#"[{"kind": "cliche", "text": "struck thirteen", "note": "Orwell."}]"#;
+        let critique = Critique {
+            annotations: parse_annotations(reply, &lines[0], 0, &RedactionMap::default()).unwrap(),
+            loading: false,
+        };
+        let start = lines[0].find("struck").unwrap();
+        let notes = critique.on_line(&lines, 0);
+        assert_eq!((notes.len(), notes[0].start), (1, start));
+        assert!(critique.at(&lines, 0, start).is_some());
+        assert!(critique.on_line(&lines, 1).is_empty());
+    }
+}
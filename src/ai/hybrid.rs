@@ -1,10 +1,14 @@
+use rand::Rng;
 use std::time::Instant;
 use tokio::sync::mpsc;
+use super::local::{model_path, Sampling, SEED_MODEL};
 use super::{MarkovEngine, OpenAIClient, Suggestion};
 use crate::config::{Config, AiMode};
 
 pub struct HybridEngine {
     local: MarkovEngine,
+    /// Name of the model file the local engine was loaded from
+    model: String,
     api: OpenAIClient,
     config: Config,
     last_api_call: Option<Instant>,
@@ -12,20 +16,69 @@ pub struct HybridEngine {
 
 impl HybridEngine {
     pub fn new(config: Config) -> Self {
+        let (model, local) = match Self::load_local(&config, &config.markov_model) {
+            Some(local) => (config.markov_model.clone(), local),
+            None => (SEED_MODEL.to_string(), Self::load_local(&config, SEED_MODEL).unwrap_or_default()),
+        };
+
         Self {
-            local: MarkovEngine::new(),
+            local,
+            model,
             api: OpenAIClient::new(config.clone()),
             config,
             last_api_call: None,
         }
     }
 
-    pub fn suggest_local(&self, context: &str) -> Vec<Suggestion> {
-        self.local.suggest(context)
+    /// The named model with the author's corpus learned on top
+    fn load_local(config: &Config, name: &str) -> Option<MarkovEngine> {
+        let engine = if name == SEED_MODEL {
+            MarkovEngine::with_order(config.markov_order)
+        } else {
+            MarkovEngine::load(&model_path(name)?)?
+        };
+        let mut engine = engine.with_author_weight(config.author_weight);
+        if let Some(words) = config.dictionary.as_ref().and_then(|path| std::fs::read_to_string(path).ok()) {
+            engine.learn_dictionary(&words);
+        }
+        if let Some(dir) = &config.corpus_dir {
+            engine.learn_corpus(dir);
+        }
+        Some(engine)
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Replace the local engine with another named model. The open document
+    /// has to be learned again afterwards.
+    pub fn switch_model(&mut self, name: &str) -> bool {
+        match Self::load_local(&self.config, name) {
+            Some(local) => {
+                self.local = local;
+                self.model = name.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn model_order(&self) -> usize {
+        self.local.order()
+    }
+
+    pub fn suggest_local(&self, context: &str, sampling: Sampling, rng: &mut impl Rng) -> Vec<Suggestion> {
+        self.local.suggest(context, sampling, rng)
+    }
+
+    pub fn suggest_sentence_local(&self, context: &str, sampling: Sampling, rng: &mut impl Rng) -> Option<Suggestion> {
+        self.local.suggest_sentence(context, sampling, rng)
     }
 
-    pub fn suggest_sentence_local(&self, context: &str) -> Option<Suggestion> {
-        self.local.suggest_sentence(context)
+    /// Let the local engine learn from the open document
+    pub fn learn_document(&mut self, text: &str) {
+        self.local.learn_document(text);
     }
 
     pub fn should_call_api(&self, local_suggestions: &[Suggestion]) -> bool {
@@ -57,7 +110,7 @@ impl HybridEngine {
         let api = OpenAIClient::new(self.config.clone());
 
         tokio::spawn(async move {
-            let result = api.suggest(&context).await;
+            let result = api.suggest(&context).await.map(|(suggestion, _)| suggestion);
             let _ = tx.send(result).await;
         });
     }
@@ -67,7 +120,7 @@ impl HybridEngine {
         let api = OpenAIClient::new(self.config.clone());
 
         tokio::spawn(async move {
-            let result = api.suggest_sentence(&context).await;
+            let result = api.suggest_sentence(&context).await.map(|(suggestion, _)| suggestion);
             let _ = tx.send(result).await;
         });
     }
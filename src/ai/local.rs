@@ -1,106 +1,317 @@
+use rand::distributions::WeightedIndex;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use super::{Suggestion, SuggestionSource};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use super::tokens::{detokenize, is_closing_quote, is_terminator, is_word, tokenize, START};
+use super::vocabulary::{complete_as_typed, Vocabulary};
+use super::{stable_hash, Suggestion, SuggestionSource};
+use crate::config::config_dir;
 
 const SEED_TEXT: &str = include_str!("../../data/markov_seed.txt");
 
+pub const DEFAULT_ORDER: usize = 3;
+pub const MAX_ORDER: usize = 4;
+/// How many seed occurrences one occurrence in the author's own text is worth
+pub const DEFAULT_AUTHOR_WEIGHT: u32 = 4;
+/// The model trained on the built-in seed text, which needs no file
+pub const SEED_MODEL: &str = "seed";
+
+/// Score multiplier for each step down to a shorter context ("stupid backoff")
+const BACKOFF: f32 = 0.4;
+/// Confidence ceiling by how many context words matched, so a continuation
+/// seen after the same three words outranks one seen after just the last word
+const ORDER_CONFIDENCE: [f32; MAX_ORDER - 1] = [0.5, 0.7, 0.85];
+/// Sentence suggestions give up after this many tokens without a terminator
+const MAX_SENTENCE_TOKENS: usize = 40;
+const MIN_SENTENCE_WORDS: usize = 3;
+/// Random walks tried before giving up on a sentence suggestion
+const SENTENCE_ATTEMPTS: usize = 5;
+/// Chance per sampled token, at full weirdness, of taking an unlikely
+/// transition: a twenty-token sentence at weirdness 0.2 takes about one
+const WEIRD_TURNS: f64 = 0.25;
+const RANDOM_STARTERS: &[&str] = &[
+    "suddenly", "perhaps", "meanwhile", "beneath", "through",
+    "silently", "eventually", "somewhere", "beyond", "within",
+];
+
+/// How adventurous local generation is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampling {
+    /// Below 1 sentences keep to the likeliest transitions, above 1 they
+    /// spread toward rarer ones
+    pub temperature: f32,
+    /// 0 to 1: how often suggestions jump to unlikely transitions or random
+    /// starters
+    pub weirdness: f32,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self { temperature: 1.0, weirdness: 0.1 }
+    }
+}
+
+type Continuations = Vec<(String, u32)>;
+
+/// Model files store everything learned from a corpus; the author weight and
+/// the open document belong to the running editor and are left out
+#[derive(Serialize, Deserialize)]
 pub struct MarkovEngine {
-    chains: HashMap<String, Vec<(String, u32)>>,
-    word_completions: HashMap<String, Vec<String>>,
+    /// n-gram order: suggestions look at up to `order - 1` previous words
+    order: usize,
+    /// `chains[k - 1]` maps each run of `k` lowercased words to the words
+    /// seen after it
+    chains: Vec<HashMap<String, Continuations>>,
+    #[serde(default)]
+    vocabulary: Vocabulary,
+    #[serde(skip, default = "default_author_weight")]
+    author_weight: u32,
+    /// Paragraphs of the open document as last learned, to unlearn them
+    /// once they are edited or deleted
+    #[serde(skip)]
+    document: HashMap<String, u32>,
+}
+
+fn default_author_weight() -> u32 {
+    DEFAULT_AUTHOR_WEIGHT
 }
 
 impl MarkovEngine {
     pub fn new() -> Self {
-        let mut engine = Self {
-            chains: HashMap::new(),
-            word_completions: HashMap::new(),
-        };
-        engine.train(SEED_TEXT);
+        Self::with_order(DEFAULT_ORDER)
+    }
+
+    /// An engine trained on the seed corpus with the given n-gram order,
+    /// clamped to 2 (bigrams) through `MAX_ORDER`
+    pub fn with_order(order: usize) -> Self {
+        let mut engine = Self::untrained(order);
+        engine.train(SEED_TEXT, 1);
+        engine.vocabulary.add_dictionary(None);
         engine
     }
 
-    fn train(&mut self, text: &str) {
-        let words: Vec<&str> = text.split_whitespace().collect();
+    fn untrained(order: usize) -> Self {
+        let order = order.clamp(2, MAX_ORDER);
+        Self {
+            order,
+            chains: vec![HashMap::new(); order - 1],
+            vocabulary: Vocabulary::default(),
+            author_weight: DEFAULT_AUTHOR_WEIGHT,
+            document: HashMap::new(),
+        }
+    }
 
-        // Train bigram chains
-        for window in words.windows(2) {
-            let key = window[0].to_lowercase();
-            let next = window[1].to_string();
+    /// A model trained on corpus files alone, without the seed. Each path is
+    /// a text file or a directory of them. Returns the model and how many
+    /// files were read.
+    pub fn from_corpus(paths: &[PathBuf], order: usize) -> (Self, usize) {
+        let mut engine = Self::untrained(order);
+        let mut read = 0;
+        for path in paths.iter().flat_map(|p| corpus_files(p)) {
+            if let Ok(text) = fs::read_to_string(&path) {
+                engine.train(&text, 1);
+                read += 1;
+            }
+        }
+        (engine, read)
+    }
 
-            let entry = self.chains.entry(key).or_insert_with(Vec::new);
-            if let Some(existing) = entry.iter_mut().find(|(w, _)| w == &next) {
-                existing.1 += 1;
+    /// Load a model saved with `save`
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        let mut engine: Self = serde_json::from_str(&content).ok()?;
+        // Reject files whose chains don't match their order
+        if engine.chains.len() + 1 != engine.order || !(2..=MAX_ORDER).contains(&engine.order) {
+            return None;
+        }
+        engine.vocabulary.add_dictionary(None);
+        Some(engine)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Make the words of a word list, one per line, completable
+    pub fn learn_dictionary(&mut self, words: &str) {
+        self.vocabulary.add_dictionary(Some(words));
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Weight the author's own text, from the corpus and the open document,
+    /// against the seed. At least 1.
+    pub fn with_author_weight(mut self, weight: u32) -> Self {
+        self.author_weight = weight.max(1);
+        self
+    }
+
+    /// Learn from the `.txt` and `.md` files in a directory of the author's
+    /// past work. Returns how many files were read.
+    pub fn learn_corpus(&mut self, dir: &Path) -> usize {
+        self.vocabulary.tick();
+        let mut read = 0;
+        for path in corpus_files(dir) {
+            if let Ok(text) = fs::read_to_string(&path) {
+                self.train(&text, self.author_weight);
+                read += 1;
+            }
+        }
+        read
+    }
+
+    /// Bring the engine up to date with the open document. Only paragraphs
+    /// that changed since the last call are unlearned and learned again.
+    pub fn learn_document(&mut self, text: &str) {
+        self.vocabulary.tick();
+        let paragraphs: Vec<&str> = text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()).collect();
+        let mut current: HashMap<String, u32> = HashMap::new();
+        for paragraph in &paragraphs {
+            *current.entry(paragraph.to_string()).or_default() += 1;
+        }
+
+        // Work in a fixed order, so the same edits always leave continuations
+        // in the same order and seeded sampling stays reproducible
+        let mut learned: Vec<(String, u32)> = std::mem::take(&mut self.document).into_iter().collect();
+        learned.sort();
+        for (paragraph, count) in &learned {
+            let kept = current.get(paragraph).copied().unwrap_or(0);
+            for _ in kept..*count {
+                self.untrain(paragraph, self.author_weight);
+            }
+        }
+        let mut known: HashMap<String, u32> = learned.into_iter().collect();
+        for paragraph in paragraphs {
+            let seen = known.entry(paragraph.to_string()).or_default();
+            if *seen > 0 {
+                *seen -= 1;
             } else {
-                entry.push((next, 1));
+                self.train(paragraph, self.author_weight);
             }
         }
+        self.document = current;
+    }
+
+    fn train(&mut self, text: &str, weight: u32) {
+        let tokens = tokenize(text);
+        let lower: Vec<String> = tokens.iter().map(|t| t.to_lowercase()).collect();
 
-        // Build word completions
-        for word in &words {
-            let lower = word.to_lowercase();
-            for i in 1..=lower.len().min(4) {
-                let prefix = &lower[..i];
-                let entry = self.word_completions.entry(prefix.to_string()).or_insert_with(Vec::new);
-                if !entry.contains(&lower) {
-                    entry.push(lower.clone());
+        // Train every context length from one token up to order - 1
+        for i in 1..tokens.len() {
+            let next = &tokens[i];
+            for k in 1..self.order.min(i + 1) {
+                let key = lower[i - k..i].join(" ");
+                let entry = self.chains[k - 1].entry(key).or_default();
+                if let Some(existing) = entry.iter_mut().find(|(t, _)| t == next) {
+                    existing.1 += weight;
+                } else {
+                    entry.push((next.clone(), weight));
                 }
             }
         }
+
+        for word in tokens.iter().filter(|t| is_word(t)) {
+            self.vocabulary.add(word, weight);
+        }
     }
 
-    pub fn suggest(&self, context: &str) -> Vec<Suggestion> {
-        let words: Vec<&str> = context.split_whitespace().collect();
-        let mut suggestions = Vec::new();
-        let mut rng = thread_rng();
+    /// Take back what `train` learned from `text`
+    fn untrain(&mut self, text: &str, weight: u32) {
+        let tokens = tokenize(text);
+        let lower: Vec<String> = tokens.iter().map(|t| t.to_lowercase()).collect();
+        for word in tokens.iter().filter(|t| is_word(t)) {
+            self.vocabulary.remove(word, weight);
+        }
+
+        for i in 1..tokens.len() {
+            for k in 1..self.order.min(i + 1) {
+                let key = lower[i - k..i].join(" ");
+                let Some(entry) = self.chains[k - 1].get_mut(&key) else { continue };
+                if let Some(existing) = entry.iter_mut().find(|(t, _)| *t == tokens[i]) {
+                    existing.1 = existing.1.saturating_sub(weight);
+                }
+                entry.retain(|(_, count)| *count > 0);
+                if entry.is_empty() {
+                    self.chains[k - 1].remove(&key);
+                }
+            }
+        }
+    }
 
-        if let Some(last_word) = words.last() {
-            let last_lower = last_word.to_lowercase();
+    pub fn suggest(&self, context: &str, sampling: Sampling, rng: &mut impl Rng) -> Vec<Suggestion> {
+        let mut suggestions = Vec::new();
 
+        if let Some(last_word) = context.split_whitespace().last() {
             // Check if we're mid-word (no trailing space)
             if !context.ends_with(' ') && !context.ends_with('\n') {
-                // Word completion
-                if let Some(completions) = self.word_completions.get(&last_lower) {
-                    for comp in completions.iter().take(3) {
-                        if comp.len() > last_lower.len() && comp.starts_with(&last_lower) {
-                            let suffix = &comp[last_lower.len()..];
+                // Word completion, after any opening quote or bracket
+                let typed = last_word.trim_start_matches(|c: char| !c.is_alphanumeric());
+                if !typed.is_empty() {
+                    for (word, seen) in self.vocabulary.complete(typed, 3) {
+                        if let Some(suffix) = complete_as_typed(typed, word) {
                             suggestions.push(Suggestion {
-                                text: suffix.to_string(),
-                                confidence: 0.6,
+                                text: suffix,
+                                confidence: if seen { 0.6 } else { 0.45 },
                                 source: SuggestionSource::Local,
+                                cached: false,
                             });
                         }
                     }
                 }
             } else {
-                // Next word prediction
-                if let Some(nexts) = self.chains.get(&last_lower) {
-                    let total: u32 = nexts.iter().map(|(_, c)| c).sum();
-                    let mut sorted: Vec<_> = nexts.iter().collect();
-                    sorted.sort_by(|a, b| b.1.cmp(&a.1));
-
-                    for (word, count) in sorted.into_iter().take(3) {
-                        let confidence = *count as f32 / total as f32;
-                        suggestions.push(Suggestion {
-                            text: format!(" {}", word),
-                            confidence: confidence.min(0.8),
-                            source: SuggestionSource::Local,
-                        });
+                // Next word prediction from the longest matching context.
+                // Punctuation belongs against the previous word, so after a
+                // space only words are offered.
+                let tokens = tokenize(context);
+                let mut candidates: Vec<(String, f32)> =
+                    self.predict(&tokens).into_iter().filter(|(t, _)| is_word(t)).collect();
+
+                // For a `weirdness` share of contexts, put the least likely
+                // continuation or a random starter first. The roll comes from
+                // the context rather than the RNG, so the ghost text doesn't
+                // flicker as the same context is refreshed.
+                let roll = stable_hash(context);
+                if let Some(&(_, top)) = candidates.first() {
+                    if ((roll % 1000) as f32) < sampling.weirdness * 1000.0 {
+                        let surprise = if candidates.len() > 3 && roll & (1 << 32) != 0 {
+                            candidates.pop().map(|(word, _)| word)
+                        } else {
+                            Some(RANDOM_STARTERS[(roll >> 33) as usize % RANDOM_STARTERS.len()].to_string())
+                        };
+                        candidates.retain(|(word, _)| Some(word) != surprise.as_ref());
+                        candidates.extend(surprise.map(|word| (word, top)));
+                        candidates.rotate_right(1);
                     }
                 }
+
+                for (word, confidence) in candidates.into_iter().take(3) {
+                    suggestions.push(Suggestion {
+                        text: continue_with(context, &tokens, word),
+                        confidence,
+                        source: SuggestionSource::Local,
+                        cached: false,
+                    });
+                }
             }
         }
 
-        // Add some randomness - occasionally suggest unexpected continuations
-        if suggestions.is_empty() || rng.gen_bool(0.2) {
-            let random_starters = [
-                "suddenly", "perhaps", "meanwhile", "beneath", "through",
-                "silently", "eventually", "somewhere", "beyond", "within"
-            ];
-            if let Some(word) = random_starters.choose(&mut rng) {
+        // Nothing seen fits, so offer something unexpected
+        if suggestions.is_empty() {
+            if let Some(word) = RANDOM_STARTERS.choose(rng) {
+                let tokens = tokenize(context);
                 suggestions.push(Suggestion {
-                    text: format!(" {}", word),
+                    text: continue_with(context, &tokens, word.to_string()),
                     confidence: 0.3,
                     source: SuggestionSource::Local,
+                    cached: false,
                 });
             }
         }
@@ -108,47 +319,150 @@ impl MarkovEngine {
         suggestions
     }
 
-    pub fn suggest_sentence(&self, context: &str) -> Option<Suggestion> {
-        let mut rng = thread_rng();
-        let words: Vec<&str> = context.split_whitespace().collect();
+    /// Candidate next tokens for the tokens so far, best first, with a
+    /// confidence that reflects the context length they were seen after.
+    /// Shorter contexts only fill in below the longest one that matched.
+    fn predict(&self, tokens: &[String]) -> Vec<(String, f32)> {
+        let mut scored: Vec<(String, f32, f32)> = Vec::new();
+        let mut discount = 1.0;
+
+        for k in (1..self.order).rev() {
+            let Some(nexts) = self.continuations(tokens, k) else { continue };
+            let total: u32 = nexts.iter().map(|(_, c)| c).sum();
+            for (token, count) in nexts {
+                if token == START || scored.iter().any(|(t, _, _)| t == token) {
+                    continue;
+                }
+                let frequency = *count as f32 / total as f32;
+                let confidence = ORDER_CONFIDENCE[k - 1] * (0.5 + 0.5 * frequency);
+                scored.push((token.clone(), discount * frequency, confidence));
+            }
+            discount *= BACKOFF;
+        }
+
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.into_iter().map(|(token, _, confidence)| (token, confidence)).collect()
+    }
+
+    /// Tokens seen after the last `k` of `tokens`, if that context was seen
+    fn continuations(&self, tokens: &[String], k: usize) -> Option<&Continuations> {
+        if tokens.len() < k {
+            return None;
+        }
+        let key = tokens[tokens.len() - k..]
+            .iter()
+            .map(|t| t.to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.chains[k - 1].get(&key)
+    }
 
-        if words.is_empty() {
+    /// The rest of the current sentence, or the next one if the context
+    /// ends with a terminator, sampled until a terminator is reached. Walks
+    /// that run into an unseen context or on too long are discarded.
+    pub fn suggest_sentence(&self, context: &str, sampling: Sampling, rng: &mut impl Rng) -> Option<Suggestion> {
+        let tokens = tokenize(context);
+        if tokens.len() < 2 {
             return None;
         }
 
-        let last_word = words.last()?.to_lowercase();
-        let mut sentence = String::new();
-        let mut current = last_word.clone();
+        let generated = (0..SENTENCE_ATTEMPTS).find_map(|_| self.generate_sentence(&tokens, sampling, rng))?;
+        Some(Suggestion {
+            text: detokenize(context, &generated),
+            confidence: 0.4,
+            source: SuggestionSource::Local,
+            cached: false,
+        })
+    }
+
+    fn generate_sentence(&self, context: &[String], sampling: Sampling, rng: &mut impl Rng) -> Option<Vec<String>> {
+        let mut tokens = context.to_vec();
+        // Keep a sentence start the context ends with, to capitalize after it
+        let start = tokens.len() - usize::from(tokens.last().is_some_and(|t| t == START));
 
-        for _ in 0..8 {
-            if let Some(nexts) = self.chains.get(&current) {
-                let total: u32 = nexts.iter().map(|(_, c)| c).sum();
-                let mut pick: u32 = rng.gen_range(0..total.max(1));
+        while tokens.len() - start < MAX_SENTENCE_TOKENS {
+            // Sample from the longest context that has been seen
+            let nexts = (1..self.order).rev().find_map(|k| self.continuations(&tokens, k))?;
+            let nexts: Vec<&(String, u32)> = nexts.iter().filter(|(t, _)| t != START).collect();
+            let weird = rng.gen_bool((sampling.weirdness as f64 * WEIRD_TURNS).clamp(0.0, 1.0));
+            let next = sample(&nexts, sampling.temperature, weird, rng)?;
+            tokens.push(next.clone());
 
-                for (word, count) in nexts {
-                    if pick < *count {
-                        sentence.push(' ');
-                        sentence.push_str(word);
-                        current = word.to_lowercase();
-                        break;
-                    }
-                    pick -= count;
+            if is_terminator(next) {
+                // Close a quotation the sentence ends inside of
+                let closing = (1..self.order)
+                    .rev()
+                    .find_map(|k| self.continuations(&tokens, k))
+                    .and_then(|nexts| nexts.iter().max_by_key(|(_, count)| *count))
+                    .filter(|(t, _)| is_closing_quote(t));
+                if let Some((quote, _)) = closing {
+                    tokens.push(quote.clone());
                 }
-            } else {
-                break;
+                let generated = tokens.split_off(start);
+                return (generated.iter().filter(|t| is_word(t)).count() >= MIN_SENTENCE_WORDS).then_some(generated);
             }
         }
+        None
+    }
+}
 
-        if sentence.len() > 10 {
-            Some(Suggestion {
-                text: sentence,
-                confidence: 0.4,
-                source: SuggestionSource::Local,
-            })
-        } else {
-            None
-        }
+/// Pick a continuation with probability proportional to its count raised to
+/// `1 / temperature`, or, for a weird turn, inversely to its count
+fn sample<'a>(nexts: &[&'a (String, u32)], temperature: f32, weird: bool, rng: &mut impl Rng) -> Option<&'a String> {
+    let max = nexts.iter().map(|(_, count)| *count).max()? as f64;
+    let exponent = 1.0 / temperature.clamp(0.1, 5.0) as f64;
+    let weights = nexts.iter().map(|(_, count)| {
+        let share = *count as f64 / max;
+        if weird { 1.0 / share } else { share.powf(exponent) }
+    });
+    let index = WeightedIndex::new(weights).ok()?;
+    Some(&nexts[index.sample(rng)].0)
+}
+
+
+/// A single word written as it follows the context, capitalized when it
+/// starts a sentence
+fn continue_with(context: &str, tokens: &[String], word: String) -> String {
+    let mut generated = vec![word];
+    if tokens.last().is_some_and(|t| t == START) {
+        generated.insert(0, START.to_string());
     }
+    detokenize(context, &generated)
+}
+
+/// The file itself, or the `.txt` and `.md` files directly inside a directory
+fn corpus_files(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+    let Ok(entries) = fs::read_dir(path) else { return Vec::new() };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt" || ext == "md"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Where named models live (`~/.config/systymn/models/<name>.json`)
+pub fn model_path(name: &str) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("models").join(format!("{}.json", name)))
+}
+
+/// The seed model, then every model file by name
+pub fn available_models() -> Vec<String> {
+    let mut names: Vec<String> = config_dir()
+        .and_then(|dir| fs::read_dir(dir.join("models")).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .filter(|name| name != SEED_MODEL)
+        .collect();
+    names.sort();
+    names.insert(0, SEED_MODEL.to_string());
+    names
 }
 
 impl Default for MarkovEngine {
@@ -156,3 +470,79 @@ impl Default for MarkovEngine {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+
+    const CALM: Sampling = Sampling { temperature: 1.0, weirdness: 0.0 };
+
+    fn first(engine: &MarkovEngine, context: &str, sampling: Sampling) -> String {
+        engine.suggest(context, sampling, &mut StdRng::seed_from_u64(0))[0].text.clone()
+    }
+
+    #[test]
+    fn learns_the_authors_corpus_and_document() {
+        let dir = TestConfigDir::new();
+        fs::write(dir.path().join("harbor.txt"), "The old lighthouse leaned into the wind.").unwrap();
+        let mut engine = MarkovEngine::new();
+
+        // The seed continues "the old" with "woman" and "church" once each
+        assert_eq!(engine.learn_corpus(dir.path()), 1);
+        assert_eq!(first(&engine, "We rowed past the old ", CALM), "lighthouse");
+
+        engine.learn_document("A lantern swung above the deck.");
+        assert_eq!(first(&engine, "My lantern ", CALM), "swung");
+
+        // Edited paragraphs are unlearned
+        engine.learn_document("A lantern hung above the deck.");
+        assert_eq!(first(&engine, "My lantern ", CALM), "hung");
+    }
+
+    #[test]
+    fn saved_models_load_by_name() {
+        let dir = TestConfigDir::new();
+        let corpus = dir.path().join("noir.txt");
+        fs::write(&corpus, "Rain hammered the neon sign outside the precinct.").unwrap();
+        let (model, files) = MarkovEngine::from_corpus(std::slice::from_ref(&corpus), 2);
+        assert_eq!(files, 1);
+
+        let path = model_path("noir").unwrap();
+        model.save(&path).unwrap();
+        assert_eq!(available_models(), ["seed", "noir"]);
+        let loaded = MarkovEngine::load(&path).expect("model");
+        assert_eq!(loaded.order(), 2);
+        assert_eq!(first(&loaded, "The sky cleared. Rain ", CALM), "hammered");
+    }
+
+    #[test]
+    fn sentences_are_whole_and_well_spaced() {
+        let engine = MarkovEngine::new();
+        let context = "The clock tower struck thirteen and nobody seemed to notice. ";
+        let sentences: Vec<String> = (0..10)
+            .filter_map(|seed| engine.suggest_sentence(context, CALM, &mut StdRng::seed_from_u64(seed)))
+            .map(|suggestion| suggestion.text)
+            .collect();
+
+        assert!(!sentences.is_empty());
+        for sentence in sentences {
+            assert!(sentence.starts_with(char::is_uppercase), "{}", sentence);
+            assert!(sentence.trim_end_matches(['"', '\u{201D}']).ends_with(['.', '!', '?']), "{}", sentence);
+            assert!(!sentence.contains(" .") && !sentence.contains(" ,") && !sentence.contains("  "), "{}", sentence);
+        }
+    }
+
+    #[test]
+    fn weirdness_puts_unlikely_words_first() {
+        let engine = MarkovEngine::new();
+        let context = "The clock tower struck thirteen. Beneath the ";
+        let weird = Sampling { weirdness: 1.0, ..CALM };
+
+        assert_ne!(first(&engine, context, weird), first(&engine, context, CALM));
+        // The same context always gets the same surprise
+        let surprise = first(&engine, context, weird);
+        let reseeded = engine.suggest(context, weird, &mut StdRng::seed_from_u64(99))[0].text.clone();
+        assert_eq!(reseeded, surprise);
+    }
+}
@@ -0,0 +1,612 @@
+//! In-process mock of the OpenAI and Anthropic HTTP APIs.
+//!
+//! Point `openai_base_url` / `anthropic_base_url` at [`MockServer::url`] and
+//! the clients talk to it exactly as they would to the real services. The
+//! server can answer with canned text or error codes, stream responses as
+//! server-sent events, record real traffic to a fixture file, or replay a
+//! fixture file without touching the network.
+//!
+//! Tests always have it; the app only with the `fixtures` feature. Canned
+//! replies exist for tests alone.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::config::{AiProvider, Config, FixtureConfig, FixtureMode};
+
+#[cfg(test)]
+const DEFAULT_REPLY: &str = "the lanterns hummed softly";
+
+/// How the mock answers requests that aren't served from fixtures
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockReply {
+    Text(String),
+    Error(u16),
+}
+
+/// One recorded exchange, matched on endpoint and request body during replay
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+    pub path: String,
+    pub request: Value,
+    pub status: u16,
+    pub content_type: String,
+    pub body: String,
+}
+
+enum Backend {
+    #[cfg(test)]
+    Canned(MockReply),
+    Record {
+        openai_upstream: String,
+        anthropic_upstream: String,
+        path: PathBuf,
+        fixtures: Vec<Fixture>,
+    },
+    Replay(Vec<Fixture>),
+}
+
+struct MockState {
+    backend: Backend,
+    #[cfg(test)]
+    requests: Vec<(String, Value)>,
+}
+
+struct HttpRequest {
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct HttpResponse {
+    status: u16,
+    content_type: String,
+    body: String,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    #[cfg(test)]
+    state: Arc<Mutex<MockState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start a server answering every request with a fixed completion
+    #[cfg(test)]
+    pub async fn start() -> io::Result<Self> {
+        Self::spawn(Backend::Canned(MockReply::Text(DEFAULT_REPLY.to_string()))).await
+    }
+
+    /// Start a server in record or replay mode. Recording forwards to the
+    /// base URLs currently in `config`.
+    pub async fn start_with_fixtures(fixtures: &FixtureConfig, config: &Config) -> io::Result<Self> {
+        let recorded: Vec<Fixture> = fs::read_to_string(&fixtures.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let backend = match fixtures.mode {
+            FixtureMode::Replay => Backend::Replay(recorded),
+            FixtureMode::Record => Backend::Record {
+                openai_upstream: config.openai_base_url.clone(),
+                anthropic_upstream: config.anthropic_base_url.clone(),
+                path: fixtures.path.clone(),
+                fixtures: recorded,
+            },
+        };
+        Self::spawn(backend).await
+    }
+
+    async fn spawn(backend: Backend) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState {
+            backend,
+            #[cfg(test)]
+            requests: Vec::new(),
+        }));
+
+        let server_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let state = server_state.clone();
+                tokio::spawn(async move {
+                    let _ = handle_connection(stream, state).await;
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            #[cfg(test)]
+            state,
+            task,
+        })
+    }
+
+    /// Base URL to use for both `openai_base_url` and `anthropic_base_url`
+    pub fn url(&self) -> String {
+        format!("http://{}/v1", self.addr)
+    }
+
+    /// Point both providers in `config` at this server
+    pub fn configure(&self, config: &mut Config) {
+        config.openai_base_url = self.url();
+        config.anthropic_base_url = self.url();
+    }
+
+    #[cfg(test)]
+    pub fn reply_with(&self, reply: MockReply) {
+        self.state.lock().unwrap().backend = Backend::Canned(reply);
+    }
+
+    /// Endpoint and JSON body of every request received so far
+    #[cfg(test)]
+    pub fn requests(&self) -> Vec<(String, Value)> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<MockState>>) -> io::Result<()> {
+    let Some(request) = read_request(&mut stream).await? else {
+        return Ok(());
+    };
+    let body: Value = serde_json::from_slice(&request.body).unwrap_or(Value::Null);
+    let provider = provider_for(&request.path);
+
+    let response = match provider {
+        None => error_response(AiProvider::OpenAI, 404, "unknown endpoint"),
+        Some(provider) if !is_authorized(provider, &request.headers) => {
+            error_response(provider, 401, "missing API key")
+        }
+        Some(provider) => respond(provider, &request, &body, &state).await,
+    };
+
+    write_response(&mut stream, response).await
+}
+
+async fn respond(
+    provider: AiProvider,
+    request: &HttpRequest,
+    body: &Value,
+    state: &Arc<Mutex<MockState>>,
+) -> HttpResponse {
+    #[cfg(test)]
+    state.lock().unwrap().requests.push((request.path.clone(), body.clone()));
+
+    // Decide what to do while holding the lock, but never across an await
+    let upstream = {
+        let state = state.lock().unwrap();
+        match &state.backend {
+            #[cfg(test)]
+            Backend::Canned(MockReply::Text(text)) => return canned_response(provider, body, text),
+            #[cfg(test)]
+            Backend::Canned(MockReply::Error(status)) => {
+                return error_response(provider, *status, "mock error");
+            }
+            Backend::Replay(fixtures) => {
+                return fixtures
+                    .iter()
+                    .find(|f| f.path == request.path && f.request == *body)
+                    .map(|f| HttpResponse {
+                        status: f.status,
+                        content_type: f.content_type.clone(),
+                        body: f.body.clone(),
+                    })
+                    .unwrap_or_else(|| error_response(provider, 501, "no fixture recorded for this request"));
+            }
+            Backend::Record { openai_upstream, anthropic_upstream, .. } => match provider {
+                AiProvider::Anthropic => anthropic_upstream.clone(),
+                _ => openai_upstream.clone(),
+            },
+        }
+    };
+
+    let response = forward(&upstream, request).await;
+
+    let mut state = state.lock().unwrap();
+    if let Backend::Record { path, fixtures, .. } = &mut state.backend {
+        fixtures.push(Fixture {
+            path: request.path.clone(),
+            request: body.clone(),
+            status: response.status,
+            content_type: response.content_type.clone(),
+            body: response.body.clone(),
+        });
+        if let Ok(json) = serde_json::to_string_pretty(fixtures) {
+            let _ = fs::write(path, json);
+        }
+    }
+    response
+}
+
+/// Pass a request on to the real API while recording
+async fn forward(upstream: &str, request: &HttpRequest) -> HttpResponse {
+    let endpoint = request.path.strip_prefix("/v1").unwrap_or(&request.path);
+    let mut builder = reqwest::Client::new()
+        .post(format!("{}{}", upstream.trim_end_matches('/'), endpoint))
+        .body(request.body.clone());
+    for name in ["authorization", "x-api-key", "anthropic-version", "content-type"] {
+        if let Some(value) = request.headers.get(name) {
+            builder = builder.header(name, value);
+        }
+    }
+
+    match builder.send().await {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            let content_type = response
+                .headers()
+                .get("content-type")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("application/json")
+                .to_string();
+            let body = response.text().await.unwrap_or_default();
+            HttpResponse { status, content_type, body }
+        }
+        Err(e) => error_response(AiProvider::OpenAI, 502, &e.to_string()),
+    }
+}
+
+fn provider_for(path: &str) -> Option<AiProvider> {
+    if path.ends_with("/chat/completions") {
+        Some(AiProvider::OpenAI)
+    } else if path.ends_with("/messages") {
+        Some(AiProvider::Anthropic)
+    } else {
+        None
+    }
+}
+
+fn is_authorized(provider: AiProvider, headers: &HashMap<String, String>) -> bool {
+    match provider {
+        AiProvider::Anthropic => headers.get("x-api-key").is_some_and(|k| !k.is_empty()),
+        _ => headers
+            .get("authorization")
+            .is_some_and(|v| v.len() > "Bearer ".len()),
+    }
+}
+
+/// Rough token count, good enough for exercising usage accounting
+#[cfg(test)]
+fn count_tokens(value: &Value) -> u64 {
+    match value {
+        Value::String(s) => s.split_whitespace().count() as u64,
+        Value::Array(items) => items.iter().map(count_tokens).sum(),
+        Value::Object(map) => map.values().map(count_tokens).sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+fn canned_response(provider: AiProvider, request: &Value, text: &str) -> HttpResponse {
+    let model = request["model"].as_str().unwrap_or("mock-model");
+    let input_tokens = count_tokens(&request["messages"]) + count_tokens(&request["system"]);
+    let output_tokens = text.split_whitespace().count() as u64;
+    let stream = request["stream"].as_bool().unwrap_or(false);
+
+    let body = match (provider, stream) {
+        (AiProvider::Anthropic, false) => json!({
+            "id": "msg_mock",
+            "type": "message",
+            "role": "assistant",
+            "model": model,
+            "content": [{ "type": "text", "text": text }],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": input_tokens, "output_tokens": output_tokens },
+        })
+        .to_string(),
+        (AiProvider::Anthropic, true) => {
+            let mut events = vec![
+                ("message_start", json!({
+                    "type": "message_start",
+                    "message": {
+                        "id": "msg_mock", "type": "message", "role": "assistant", "model": model,
+                        "content": [], "usage": { "input_tokens": input_tokens, "output_tokens": 0 },
+                    },
+                })),
+                ("content_block_start", json!({
+                    "type": "content_block_start", "index": 0,
+                    "content_block": { "type": "text", "text": "" },
+                })),
+            ];
+            for piece in text.split_inclusive(' ') {
+                events.push(("content_block_delta", json!({
+                    "type": "content_block_delta", "index": 0,
+                    "delta": { "type": "text_delta", "text": piece },
+                })));
+            }
+            events.push(("content_block_stop", json!({ "type": "content_block_stop", "index": 0 })));
+            events.push(("message_delta", json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "end_turn" },
+                "usage": { "output_tokens": output_tokens },
+            })));
+            events.push(("message_stop", json!({ "type": "message_stop" })));
+
+            let body = events
+                .into_iter()
+                .map(|(event, data)| format!("event: {}\ndata: {}\n\n", event, data))
+                .collect();
+            return HttpResponse { status: 200, content_type: "text/event-stream".to_string(), body };
+        }
+        (_, false) => json!({
+            "id": "chatcmpl-mock",
+            "object": "chat.completion",
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": text },
+                "finish_reason": "stop",
+            }],
+            "usage": {
+                "prompt_tokens": input_tokens,
+                "completion_tokens": output_tokens,
+                "total_tokens": input_tokens + output_tokens,
+            },
+        })
+        .to_string(),
+        (_, true) => {
+            let mut body: String = text
+                .split_inclusive(' ')
+                .map(|piece| {
+                    let chunk = json!({
+                        "id": "chatcmpl-mock",
+                        "object": "chat.completion.chunk",
+                        "model": model,
+                        "choices": [{ "index": 0, "delta": { "content": piece }, "finish_reason": null }],
+                    });
+                    format!("data: {}\n\n", chunk)
+                })
+                .collect();
+            body.push_str("data: [DONE]\n\n");
+            return HttpResponse { status: 200, content_type: "text/event-stream".to_string(), body };
+        }
+    };
+
+    HttpResponse { status: 200, content_type: "application/json".to_string(), body }
+}
+
+fn error_response(provider: AiProvider, status: u16, message: &str) -> HttpResponse {
+    let kind = match status {
+        400 => "invalid_request_error",
+        401 => "authentication_error",
+        404 => "not_found_error",
+        429 => "rate_limit_error",
+        529 => "overloaded_error",
+        _ => "api_error",
+    };
+    let body = match provider {
+        AiProvider::Anthropic => json!({
+            "type": "error",
+            "error": { "type": kind, "message": message },
+        }),
+        _ => json!({
+            "error": { "message": message, "type": kind, "code": status },
+        }),
+    };
+    HttpResponse { status, content_type: "application/json".to_string(), body: body.to_string() }
+}
+
+async fn read_request(stream: &mut TcpStream) -> io::Result<Option<HttpRequest>> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let path = lines
+        .next()
+        .and_then(|request_line| request_line.split_whitespace().nth(1))
+        .unwrap_or("/")
+        .to_string();
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let content_length: usize = headers
+        .get("content-length")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    Ok(Some(HttpRequest { path, headers, body }))
+}
+
+async fn write_response(stream: &mut TcpStream, response: HttpResponse) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        _ => "Error",
+    };
+
+    // Event streams are close-delimited, like the chunked streams of the real APIs
+    let head = if response.content_type == "text/event-stream" {
+        format!(
+            "HTTP/1.1 {} {}\r\ncontent-type: {}\r\ncache-control: no-cache\r\nconnection: close\r\n\r\n",
+            response.status, reason, response.content_type
+        )
+    } else {
+        format!(
+            "HTTP/1.1 {} {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            response.status, reason, response.content_type, response.body.len()
+        )
+    };
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{AnthropicClient, OpenAIClient};
+
+    fn test_config(server: &MockServer) -> Config {
+        let mut config = Config {
+            openai_api_key: Some("test-openai-key".to_string()),
+            anthropic_api_key: Some("test-anthropic-key".to_string()),
+            ..Config::default()
+        };
+        server.configure(&mut config);
+        config
+    }
+
+    #[tokio::test]
+    async fn both_apis_answer_with_usage() {
+        let server = MockServer::start().await.unwrap();
+        let config = test_config(&server);
+
+        let (suggestion, usage) = OpenAIClient::new(config.clone()).suggest("The clock tower").await.unwrap();
+        assert_eq!(suggestion.text, format!(" {}", DEFAULT_REPLY));
+        assert_eq!(usage.output_tokens, 4);
+        assert!(usage.input_tokens > 0);
+
+        server.reply_with(MockReply::Text("like a moth to lamplight".to_string()));
+        let (suggestion, _) = AnthropicClient::new(config).suggest("The clock tower").await.unwrap();
+        assert_eq!(suggestion.text, " like a moth to lamplight");
+
+        let paths: Vec<String> = server.requests().into_iter().map(|(path, _)| path).collect();
+        assert_eq!(paths, ["/v1/chat/completions", "/v1/messages"]);
+    }
+
+    #[tokio::test]
+    async fn error_codes_produce_no_suggestion() {
+        let server = MockServer::start().await.unwrap();
+        let config = test_config(&server);
+        for status in [401, 429, 500] {
+            server.reply_with(MockReply::Error(status));
+            assert!(OpenAIClient::new(config.clone()).suggest("The clock").await.is_none(), "{}", status);
+            assert!(AnthropicClient::new(config.clone()).suggest("The clock").await.is_none(), "{}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_api_key_is_rejected() {
+        let server = MockServer::start().await.unwrap();
+        let response = reqwest::Client::new()
+            .post(format!("{}/messages", server.url()))
+            .json(&json!({ "model": "claude-3-haiku-20240307", "messages": [] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["type"], "authentication_error");
+    }
+
+    #[tokio::test]
+    async fn streams_server_sent_events() {
+        let server = MockServer::start().await.unwrap();
+        server.reply_with(MockReply::Text("rain on glass".to_string()));
+        let client = reqwest::Client::new();
+
+        let openai = client
+            .post(format!("{}/chat/completions", server.url()))
+            .bearer_auth("test-openai-key")
+            .json(&json!({ "model": "gpt-4o-mini", "stream": true, "messages": [] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(openai.headers()["content-type"], "text/event-stream");
+        let body = openai.text().await.unwrap();
+        let text: String = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter(|data| *data != "[DONE]")
+            .map(|data| serde_json::from_str::<Value>(data).unwrap())
+            .map(|chunk| chunk["choices"][0]["delta"]["content"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(text, "rain on glass");
+        assert!(body.ends_with("data: [DONE]\n\n"));
+
+        let anthropic = client
+            .post(format!("{}/messages", server.url()))
+            .header("x-api-key", "test-anthropic-key")
+            .json(&json!({ "model": "claude-3-haiku-20240307", "stream": true, "messages": [] }))
+            .send()
+            .await
+            .unwrap();
+        let body = anthropic.text().await.unwrap();
+        let text: String = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .map(|data| serde_json::from_str::<Value>(data).unwrap())
+            .filter(|event| event["type"] == "content_block_delta")
+            .map(|event| event["delta"]["text"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(text, "rain on glass");
+        assert!(body.contains("event: message_stop"));
+    }
+
+    #[tokio::test]
+    async fn records_then_replays_fixtures() {
+        let upstream = MockServer::start().await.unwrap();
+        upstream.reply_with(MockReply::Text("the bells forgot their names".to_string()));
+        let path = std::env::temp_dir().join(format!("systymn-fixtures-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        // Record through a proxy whose upstream is the first mock
+        let record = FixtureConfig { mode: FixtureMode::Record, path: path.clone() };
+        let recorder = MockServer::start_with_fixtures(&record, &test_config(&upstream)).await.unwrap();
+        let client = OpenAIClient::new(test_config(&recorder));
+        let (suggestion, _) = client.suggest("The clock tower").await.unwrap();
+        assert_eq!(suggestion.text, " the bells forgot their names");
+        drop(recorder);
+        drop(upstream);
+
+        // Replay with no upstream at all
+        let replay = FixtureConfig { mode: FixtureMode::Replay, path: path.clone() };
+        let player = MockServer::start_with_fixtures(&replay, &Config::default()).await.unwrap();
+        let client = OpenAIClient::new(test_config(&player));
+        let (suggestion, _) = client.suggest("The clock tower").await.unwrap();
+        assert_eq!(suggestion.text, " the bells forgot their names");
+
+        // A request that was never recorded is refused
+        assert!(client.suggest("The clock tower at all").await.is_none());
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -2,27 +2,99 @@ mod local;
 mod api;
 mod anthropic;
 mod hybrid;
+mod cache;
+mod usage;
+mod redact;
+mod audit;
+#[cfg(any(test, feature = "fixtures"))]
+mod mock;
+mod chat;
+mod critique;
+mod summary;
+mod style;
+mod analytics;
+mod vocabulary;
+mod tokens;
 
-pub use local::MarkovEngine;
+pub use local::{available_models, model_path, MarkovEngine, Sampling, DEFAULT_ORDER as DEFAULT_MARKOV_ORDER};
 pub use api::OpenAIClient;
 pub use anthropic::AnthropicClient;
 pub use hybrid::HybridEngine;
+pub use cache::{CacheKey, CompletionCache};
+pub use usage::UsageTracker;
+pub use redact::{RedactionMap, Redactor};
+pub use audit::{AuditLog, AuditViewer};
+#[cfg(any(test, feature = "fixtures"))]
+pub use mock::MockServer;
+pub use chat::{chat_system_prompt, ChatMessage, ChatPanel, ChatRole};
+pub use critique::{parse_annotations, Annotation, Critique, CritiqueKind};
+pub use summary::{split_chapters, ChapterSummary, SummaryStore};
+pub use style::StyleProfile;
+pub use analytics::{Outcome, Slot, SuggestionAnalytics};
+
+use serde::{Deserialize, Serialize};
+use crate::config::AiProvider;
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust releases, so
+/// hashes saved to disk stay valid after a toolchain upgrade
+pub fn stable_hash(text: &str) -> u64 {
+    text.bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
 
 #[derive(Debug, Clone)]
 pub struct Suggestion {
     pub text: String,
     pub confidence: f32,
     pub source: SuggestionSource,
+    /// Served from the completion cache rather than a fresh API call
+    pub cached: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum SuggestionSource {
     Local,
     Api,
 }
 
+/// Token counts reported by a provider for a single request
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// Text and token usage from one provider call
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub usage: TokenUsage,
+}
+
 #[derive(Debug)]
 pub enum ApiResponse {
     WordSuggestion(Option<Suggestion>),
     SentenceSuggestion(Option<Suggestion>),
+    /// Answer to the latest question in the chat panel
+    ChatReply(Option<String>),
+    /// Critique annotations for the analyzed passage; `None` if the request
+    /// failed or the reply wasn't valid JSON
+    Critique(Option<Vec<Annotation>>),
+    /// One chapter of a summarization run; `summary` is `None` on failure
+    ChapterSummary {
+        title: String,
+        hash: String,
+        summary: Option<String>,
+    },
+    Usage {
+        provider: AiProvider,
+        model: String,
+        usage: TokenUsage,
+    },
 }
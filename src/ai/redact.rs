@@ -0,0 +1,185 @@
+use regex::Regex;
+
+use crate::config::RedactionConfig;
+
+const URL_PATTERN: &str = r"(?i)\b(?:https?://|www\.)[^\s<>]+[^\s<>.,;:!?)\]'\x22]";
+const EMAIL_PATTERN: &str = r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b";
+const PHONE_PATTERN: &str = r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)\s?|\d{2,4}[\s.-])\d{3,4}[\s.-]\d{3,4}\b";
+
+/// Placeholder mapping for one redacted context, used to put the real
+/// values back into the provider's answer
+#[derive(Debug, Clone, Default)]
+pub struct RedactionMap {
+    replacements: Vec<(String, String)>,
+}
+
+impl RedactionMap {
+    pub fn is_empty(&self) -> bool {
+        self.replacements.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.replacements.len()
+    }
+
+    /// Swap placeholders in `text` back for the values they stand for
+    pub fn restore(&self, text: &str) -> String {
+        let mut restored = text.to_string();
+        for (placeholder, original) in &self.replacements {
+            restored = restored.replace(placeholder, original);
+        }
+        restored
+    }
+
+    fn placeholder_for(&mut self, label: &str, original: &str) -> String {
+        if let Some((placeholder, _)) = self.replacements.iter().find(|(_, o)| o == original) {
+            return placeholder.clone();
+        }
+        let index = self.replacements
+            .iter()
+            .filter(|(p, _)| p.starts_with(&format!("[{}_", label)))
+            .count() + 1;
+        let placeholder = format!("[{}_{}]", label, index);
+        self.replacements.push((placeholder.clone(), original.to_string()));
+        placeholder
+    }
+}
+
+/// Masks emails, phone numbers, URLs, user-listed terms and custom patterns
+/// in text that is about to leave the machine
+pub struct Redactor {
+    rules: Vec<(&'static str, Regex)>,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactionConfig) -> Self {
+        let mut rules = Vec::new();
+        if !config.enabled {
+            return Self { rules };
+        }
+
+        // Earlier rules win overlaps, so URLs go first and their digits and
+        // @-signs aren't picked up as phones or emails
+        if config.urls {
+            rules.push(("URL", Regex::new(URL_PATTERN).expect("valid URL pattern")));
+        }
+        if config.emails {
+            rules.push(("EMAIL", Regex::new(EMAIL_PATTERN).expect("valid email pattern")));
+        }
+        if config.phone_numbers {
+            rules.push(("PHONE", Regex::new(PHONE_PATTERN).expect("valid phone pattern")));
+        }
+
+        let terms: Vec<String> = config.terms
+            .iter()
+            .map(|t| t.trim())
+            .filter(|t| !t.is_empty())
+            .map(term_pattern)
+            .collect();
+        if !terms.is_empty() {
+            if let Ok(re) = Regex::new(&format!("(?i)(?:{})", terms.join("|"))) {
+                rules.push(("NAME", re));
+            }
+        }
+
+        // Invalid user patterns are skipped rather than failing startup
+        for pattern in &config.patterns {
+            if let Ok(re) = Regex::new(pattern) {
+                rules.push(("REDACTED", re));
+            }
+        }
+
+        Self { rules }
+    }
+
+    pub fn redact(&self, text: &str) -> (String, RedactionMap) {
+        let mut map = RedactionMap::default();
+        let redacted = self.redact_with(text, &mut map);
+        (redacted, map)
+    }
+
+    /// Redact `text` into an existing map, so several texts sent together
+    /// share one set of placeholders
+    pub fn redact_with(&self, text: &str, map: &mut RedactionMap) -> String {
+        // Every rule matches against the original text, so no rule sees
+        // another's placeholders; overlapping matches go to the earlier rule
+        let mut spans: Vec<(usize, usize, &str)> = Vec::new();
+        for (label, re) in &self.rules {
+            for found in re.find_iter(text).filter(|m| !m.is_empty()) {
+                let overlaps = spans
+                    .iter()
+                    .any(|&(start, end, _)| found.start() < end && start < found.end());
+                if !overlaps {
+                    spans.push((found.start(), found.end(), label));
+                }
+            }
+        }
+        spans.sort_by_key(|&(start, _, _)| start);
+
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        for (start, end, label) in spans {
+            redacted.push_str(&text[last..start]);
+            redacted.push_str(&map.placeholder_for(label, &text[start..end]));
+            last = end;
+        }
+        redacted.push_str(&text[last..]);
+        redacted
+    }
+}
+
+/// A term as a pattern that only needs a word boundary at edges that are
+/// word characters, so `C++` and `@handle` still match
+fn term_pattern(term: &str) -> String {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    format!(
+        "{}{}{}",
+        if is_word(term.chars().next()) { r"\b" } else { "" },
+        regex::escape(term),
+        if is_word(term.chars().last()) { r"\b" } else { "" },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(terms: &[&str], patterns: &[&str]) -> Redactor {
+        Redactor::new(&RedactionConfig {
+            terms: terms.iter().map(|t| t.to_string()).collect(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            ..RedactionConfig::default()
+        })
+    }
+
+    #[test]
+    fn redacted_text_round_trips() {
+        let text = "Mail ada@example.com or see https://example.com/a?b=1, then call 555-123-4567. Ada wrote ada@example.com.";
+        let (redacted, map) = redactor(&["Ada"], &[]).redact(text);
+        assert_eq!(
+            redacted,
+            "Mail [EMAIL_1] or see [URL_1], then call [PHONE_1]. [NAME_1] wrote [EMAIL_1]."
+        );
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.restore(&redacted), text);
+
+        let (unchanged, map) = Redactor::new(&RedactionConfig { enabled: false, ..RedactionConfig::default() }).redact(text);
+        assert_eq!(unchanged, text);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn rules_never_touch_each_others_placeholders() {
+        let text = "EMAIL 42 times to bo@example.org";
+        let (redacted, map) = redactor(&["email"], &[r"\d+"]).redact(text);
+        assert_eq!(redacted, "[NAME_1] [REDACTED_1] times to [EMAIL_1]");
+        assert_eq!(map.restore(&redacted), text);
+    }
+
+    #[test]
+    fn terms_with_symbols_at_the_edges_match() {
+        let (redacted, map) = redactor(&["C++", "@handle", "Bo"], &[]).redact("Bo codes C++ as @handle, not Bob.");
+        assert_eq!(redacted, "[NAME_1] codes [NAME_2] as [NAME_3], not Bob.");
+        assert_eq!(map.restore(&redacted), "Bo codes C++ as @handle, not Bob.");
+    }
+}
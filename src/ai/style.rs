@@ -0,0 +1,224 @@
+use std::collections::HashSet;
+
+/// Documents shorter than this don't say enough about their author
+const MIN_WORDS: usize = 50;
+/// Segment length for the mean type-token ratio, which keeps vocabulary
+/// richness comparable between short and long documents
+const TTR_SEGMENT: usize = 100;
+
+const PAST_MARKERS: &[&str] = &[
+    "was", "were", "had", "did", "said", "went", "came", "saw", "knew", "thought",
+    "took", "made", "felt", "told", "found", "began", "stood", "sat", "ran", "left",
+];
+const PRESENT_MARKERS: &[&str] = &[
+    "is", "are", "am", "has", "does", "says", "goes", "comes", "sees", "knows",
+    "thinks", "takes", "makes", "feels", "tells", "finds", "begins", "stands", "sits", "runs",
+];
+const FIRST_PERSON: &[&str] = &["i", "me", "my", "mine", "myself", "we", "us", "our", "ours"];
+const SECOND_PERSON: &[&str] = &["you", "your", "yours", "yourself"];
+const THIRD_PERSON: &[&str] = &[
+    "he", "him", "his", "himself", "she", "her", "hers", "herself", "they", "them", "their", "theirs",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tense {
+    Past,
+    Present,
+    Mixed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointOfView {
+    First,
+    Second,
+    Third,
+}
+
+/// A local fingerprint of the author's voice, computed from the document
+/// itself and sent with AI prompts so suggestions match it
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleProfile {
+    pub avg_sentence_length: f32,
+    /// Mean type-token ratio over 100-word segments, 0 to 1
+    pub vocabulary_richness: f32,
+    /// Share of words inside quotation marks
+    pub dialogue_ratio: f32,
+    pub tense: Tense,
+    pub pov: PointOfView,
+}
+
+impl StyleProfile {
+    pub fn analyze(text: &str) -> Option<Self> {
+        let (narration, dialogue_words) = split_dialogue(text);
+        let words = words(text);
+        if words.len() < MIN_WORDS {
+            return None;
+        }
+
+        Some(Self {
+            avg_sentence_length: words.len() as f32 / count_sentences(text).max(1) as f32,
+            vocabulary_richness: mean_type_token_ratio(&words),
+            dialogue_ratio: dialogue_words as f32 / words.len() as f32,
+            tense: detect_tense(&narration),
+            pov: detect_pov(&narration),
+        })
+    }
+
+    /// Prompt instructions describing the profile. Figures are rounded so the
+    /// text, and the cache keys built from it, only change when the style does.
+    pub fn describe(&self) -> String {
+        let richness = match self.vocabulary_richness {
+            r if r < 0.55 => "plain, repetitive",
+            r if r < 0.7 => "moderately varied",
+            _ => "rich, varied",
+        };
+        let tense = match self.tense {
+            Tense::Past => "past tense",
+            Tense::Present => "present tense",
+            Tense::Mixed => "a mix of past and present tense",
+        };
+        let pov = match self.pov {
+            PointOfView::First => "first person",
+            PointOfView::Second => "second person",
+            PointOfView::Third => "third person",
+        };
+        format!(
+            "Match the author's style: sentences average about {} words, vocabulary is {}, \
+             roughly {}% of the text is dialogue, and it is written in {}, {}.",
+            self.avg_sentence_length.round() as u32,
+            richness,
+            (self.dialogue_ratio * 20.0).round() as u32 * 5,
+            tense,
+            pov,
+        )
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'').to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+/// Narration with quoted speech removed, and the number of words quoted
+fn split_dialogue(text: &str) -> (String, usize) {
+    let mut narration = String::new();
+    let mut quote = String::new();
+    let mut dialogue_words = 0;
+    let mut in_quote = false;
+
+    for c in text.chars() {
+        let closes = match c {
+            '"' => in_quote,
+            '\u{201D}' => true,
+            _ => false,
+        };
+        if closes {
+            in_quote = false;
+            dialogue_words += quote.split_whitespace().count();
+            quote.clear();
+            narration.push(' ');
+        } else if c == '"' || c == '\u{201C}' {
+            in_quote = true;
+        } else if in_quote {
+            quote.push(c);
+        } else {
+            narration.push(c);
+        }
+    }
+    dialogue_words += quote.split_whitespace().count();
+    (narration, dialogue_words)
+}
+
+fn count_sentences(text: &str) -> usize {
+    let mut count = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if matches!(c, '.' | '!' | '?' | '\u{2026}') {
+            // Treat runs like "?!" or "..." as one terminator
+            while chars.peek().is_some_and(|n| matches!(n, '.' | '!' | '?' | '"' | '\u{201D}')) {
+                chars.next();
+            }
+            if chars.peek().is_none_or(|n| n.is_whitespace()) {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+fn mean_type_token_ratio(words: &[String]) -> f32 {
+    let segments: Vec<&[String]> = if words.len() < TTR_SEGMENT {
+        vec![words]
+    } else {
+        words.chunks_exact(TTR_SEGMENT).collect()
+    };
+    let total: f32 = segments
+        .iter()
+        .map(|segment| segment.iter().collect::<HashSet<_>>().len() as f32 / segment.len() as f32)
+        .sum();
+    total / segments.len() as f32
+}
+
+fn detect_tense(narration: &str) -> Tense {
+    let (mut past, mut present) = (0, 0);
+    for word in words(narration) {
+        if PAST_MARKERS.contains(&word.as_str()) || (word.len() > 4 && word.ends_with("ed")) {
+            past += 1;
+        } else if PRESENT_MARKERS.contains(&word.as_str()) {
+            present += 1;
+        }
+    }
+
+    if past > present * 2 {
+        Tense::Past
+    } else if present > past * 2 {
+        Tense::Present
+    } else {
+        Tense::Mixed
+    }
+}
+
+fn detect_pov(narration: &str) -> PointOfView {
+    let (mut first, mut second, mut third) = (0, 0, 0);
+    for word in words(narration) {
+        if FIRST_PERSON.contains(&word.as_str()) {
+            first += 1;
+        } else if SECOND_PERSON.contains(&word.as_str()) {
+            second += 1;
+        } else if THIRD_PERSON.contains(&word.as_str()) {
+            third += 1;
+        }
+    }
+
+    // Third-person narrators still say "they" about others, so first and
+    // second person only need to hold their own against it
+    if first > second && first * 2 >= third {
+        PointOfView::First
+    } else if second > first && second * 2 >= third {
+        PointOfView::Second
+    } else {
+        PointOfView::Third
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_tense_person_and_dialogue() {
+        let text = "I walk to the harbour every morning. \"Are you lost?\" the ferryman asks me. \
+                    I tell him I am waiting for my sister. She never comes, and I know she never will. "
+            .repeat(3);
+        let profile = StyleProfile::analyze(&text).expect("long enough");
+        assert_eq!((profile.tense, profile.pov), (Tense::Present, PointOfView::First));
+        assert!(profile.dialogue_ratio > 0.0 && profile.dialogue_ratio < 0.2);
+
+        let description = profile.describe();
+        assert!(description.starts_with("Match the author's style"));
+        assert!(description.contains("present tense, first person"), "{}", description);
+        assert!(StyleProfile::analyze("Too short to tell.").is_none());
+    }
+}
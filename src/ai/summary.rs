@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::stable_hash;
+use crate::buffer::heading_title;
+
+pub const SUMMARY_SYSTEM_PROMPT: &str = "You summarize chapters of a manuscript for the author's own reference. \
+Write three to five plain sentences covering what happens, who is involved and which threads are left open. \
+Output only the summary, with no preamble.";
+
+/// A stretch of the document from one Markdown heading to the next
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    pub title: String,
+    pub start_line: usize,
+    pub text: String,
+}
+
+impl Chapter {
+    pub fn hash(&self) -> String {
+        content_hash(self.text.trim_end())
+    }
+}
+
+fn content_hash(text: &str) -> String {
+    format!("{:016x}", stable_hash(text))
+}
+
+/// Split the document on Markdown headings. Text before the first heading
+/// becomes an untitled opening chapter when it isn't blank.
+pub fn split_chapters(lines: &[String]) -> Vec<Chapter> {
+    let mut chapters: Vec<Chapter> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let Some(title) = heading_title(line) {
+            chapters.push(Chapter {
+                title: title.to_string(),
+                start_line: i,
+                text: String::new(),
+            });
+        } else if chapters.is_empty() {
+            chapters.push(Chapter {
+                title: "Opening".to_string(),
+                start_line: 0,
+                text: String::new(),
+            });
+        }
+
+        let chapter = chapters.last_mut().expect("chapter just pushed");
+        chapter.text.push_str(line);
+        chapter.text.push('\n');
+    }
+
+    chapters.retain(|c| !c.text.trim().is_empty());
+    chapters
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChapterSummary {
+    pub title: String,
+    pub hash: String,
+    pub summary: String,
+}
+
+/// Chapter summaries for one document, kept in a sidecar file beside it
+/// (`novel.md` → `novel.md.summaries.json`)
+#[derive(Debug, Default)]
+pub struct SummaryStore {
+    path: Option<PathBuf>,
+    summaries: Vec<ChapterSummary>,
+    /// Chapters still being summarized by the current run
+    pub pending: usize,
+    /// Chapters the current run failed to summarize
+    pub failed: usize,
+}
+
+impl SummaryStore {
+    pub fn load(document: Option<&Path>) -> Self {
+        let path = document.map(sidecar_path);
+        let summaries = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            summaries,
+            ..Self::default()
+        }
+    }
+
+    /// Give an unsaved document's summaries a home once it is saved
+    pub fn attach(&mut self, document: &Path) {
+        self.path = Some(sidecar_path(document));
+        self.save();
+    }
+
+    /// Chapters whose text has changed since they were last summarized
+    pub fn stale<'a>(&self, chapters: &'a [Chapter]) -> Vec<&'a Chapter> {
+        chapters
+            .iter()
+            .filter(|c| self.get(&c.hash()).is_none())
+            .collect()
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&ChapterSummary> {
+        self.summaries.iter().find(|s| s.hash == hash)
+    }
+
+    pub fn insert(&mut self, summary: ChapterSummary) {
+        if self.get(&summary.hash).is_none() {
+            self.summaries.push(summary);
+        }
+    }
+
+    /// Drop summaries of chapters that no longer exist and store the rest
+    /// in document order
+    pub fn retain(&mut self, chapters: &[Chapter]) {
+        let mut ordered = Vec::new();
+        for chapter in chapters {
+            let hash = chapter.hash();
+            if let Some(summary) = self.summaries.iter().position(|s| s.hash == hash) {
+                ordered.push(self.summaries.remove(summary));
+            }
+        }
+        self.summaries = ordered;
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else { return };
+        if let Ok(content) = serde_json::to_string_pretty(&self.summaries) {
+            let _ = fs::write(path, content);
+        }
+    }
+
+    /// Summaries of the chapters before the one containing `line`, as a
+    /// compact memory of the story so far. None unless every one of them
+    /// has an up-to-date summary, so an edited chapter is never left out.
+    pub fn memory_before(&self, chapters: &[Chapter], line: usize) -> Option<String> {
+        let memory: Option<Vec<String>> = chapters
+            .iter()
+            .take_while(|c| c.start_line + c.text.lines().count() <= line)
+            .map(|c| self.get(&c.hash()).map(|s| format!("- {}: {}", s.title, s.summary)))
+            .collect();
+        memory.filter(|m| !m.is_empty()).map(|m| m.join("\n"))
+    }
+}
+
+fn sidecar_path(document: &Path) -> PathBuf {
+    let mut name = document.file_name().unwrap_or_default().to_os_string();
+    name.push(".summaries.json");
+    document.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn chapters_start_only_at_real_headings() {
+        let chapters = split_chapters(&lines("Prologue.\n# One\n#hashtag and #\n#\n## Two \nEnd."));
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Opening", "One", "Two"]);
+        assert_eq!(chapters[1].text, "# One\n#hashtag and #\n#\n");
+    }
+
+    #[test]
+    fn memory_needs_every_earlier_chapter_summarized() {
+        let mut chapters = split_chapters(&lines("# One\nFerry.\n# Two\nClock.\n# Three\nCat."));
+        let mut store = SummaryStore::default();
+        for chapter in &chapters[..2] {
+            store.insert(ChapterSummary {
+                title: chapter.title.clone(),
+                hash: chapter.hash(),
+                summary: format!("{} happens.", chapter.title),
+            });
+        }
+        assert_eq!(
+            store.memory_before(&chapters, 5).as_deref(),
+            Some("- One: One happens.\n- Two: Two happens.")
+        );
+        assert_eq!(store.memory_before(&chapters, 1), None);
+
+        chapters[0].text.push_str("Fog.\n");
+        assert_eq!(store.memory_before(&chapters, 5), None);
+        assert_eq!(store.stale(&chapters).len(), 2);
+    }
+}
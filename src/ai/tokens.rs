@@ -0,0 +1,178 @@
+/// Marks the start of a sentence in a token stream. The tokenizer never
+/// produces it from text, since `<` is always a token of its own.
+pub const START: &str = "<s>";
+
+const TERMINATORS: &[char] = &['.', '!', '?', '\u{2026}'];
+/// Written without a space before them
+const CLOSING: &[char] = &[
+    '.', ',', ';', ':', '!', '?', '\u{2026}', ')', ']', '}', '\u{201D}', '\u{2019}', '%',
+];
+/// Written without a space after them
+const OPENING: &[char] = &['(', '[', '{', '\u{201C}', '\u{2018}'];
+/// Written without a space on either side
+const DASHES: &[char] = &['-', '\u{2013}', '\u{2014}'];
+/// Punctuation that repeats into a single token ("...", "?!", "--")
+const RUNS: &[char] = &['.', '!', '?', '-'];
+
+pub fn is_word(token: &str) -> bool {
+    token.chars().next().is_some_and(char::is_alphanumeric)
+}
+
+/// Whether the token ends a sentence: ".", "?!", "..." and the like
+pub fn is_terminator(token: &str) -> bool {
+    token.chars().last().is_some_and(|c| TERMINATORS.contains(&c))
+}
+
+pub fn is_closing_quote(token: &str) -> bool {
+    matches!(token, "\"" | "\u{201D}" | "\u{2019}" | ")")
+}
+
+fn joins_word(c: char) -> bool {
+    matches!(c, '\'' | '\u{2019}' | '-')
+}
+
+/// Split text into words and punctuation, with `START` before each sentence:
+/// at the beginning, after a terminator and any closing quotes, and at each
+/// line break, since the editor soft-wraps and hard breaks end paragraphs.
+/// Sentence-initial words are lowercased, except "I", so their counts merge
+/// with the same word mid-sentence; `detokenize` capitalizes them again.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = vec![START.to_string()];
+    let mut sentence_ended = false;
+    // Until the sentence's first word, past any opening quotes
+    let mut sentence_start = true;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            if (sentence_ended || c == '\n') && tokens.last().is_some_and(|t| t != START) {
+                tokens.push(START.to_string());
+                sentence_start = true;
+            }
+            sentence_ended = false;
+            continue;
+        }
+
+        let token = if c.is_alphanumeric() {
+            let mut word = c.to_string();
+            while let Some(&next) = chars.peek() {
+                if next.is_alphanumeric() {
+                    word.push(next);
+                } else if joins_word(next) {
+                    // Only inside a word: "don't", "well-known", not "dogs'" or "--"
+                    let mut ahead = chars.clone();
+                    ahead.next();
+                    if !ahead.peek().is_some_and(|a| a.is_alphanumeric()) {
+                        break;
+                    }
+                    word.push(next);
+                } else {
+                    break;
+                }
+                chars.next();
+            }
+            if sentence_start && word != "I" && !word.starts_with("I'") {
+                word = word.to_lowercase();
+            }
+            sentence_start = false;
+            word
+        } else {
+            let mut punctuation = c.to_string();
+            if RUNS.contains(&c) {
+                let continues = |next: &char| if c == '-' { *next == '-' } else { TERMINATORS.contains(next) };
+                while chars.peek().is_some_and(continues) {
+                    punctuation.push(chars.next().unwrap_or(c));
+                }
+            }
+            punctuation
+        };
+
+        if is_terminator(&token) {
+            sentence_ended = true;
+        }
+        tokens.push(token);
+    }
+
+    if sentence_ended {
+        tokens.push(START.to_string());
+    }
+    tokens
+}
+
+/// Write generated tokens as they should follow `context`: spaced between
+/// words, punctuation against the word it belongs to, and sentences
+/// capitalized
+pub fn detokenize(context: &str, tokens: &[String]) -> String {
+    let paragraph = context.rsplit('\n').next().unwrap_or_default();
+    let mut quote_open = paragraph.matches('"').count() % 2 == 1;
+    let mut glue = match context.chars().last() {
+        None => true,
+        Some(c) => {
+            c.is_whitespace() || OPENING.contains(&c) || DASHES.contains(&c) || (c == '"' && quote_open)
+        }
+    };
+    let mut capitalize = false;
+    let mut out = String::new();
+
+    for token in tokens {
+        if token == START {
+            capitalize = true;
+            continue;
+        }
+        let first = token.chars().next().unwrap_or(' ');
+        let (attaches_left, attaches_right) = if token == "\"" {
+            (quote_open, !quote_open)
+        } else if DASHES.contains(&first) {
+            (true, true)
+        } else {
+            (CLOSING.contains(&first), OPENING.contains(&first))
+        };
+
+        if !glue && !attaches_left {
+            out.push(' ');
+        }
+        if capitalize && is_word(token) {
+            let mut chars = token.chars();
+            out.extend(chars.next().into_iter().flat_map(char::to_uppercase));
+            out.push_str(chars.as_str());
+            capitalize = false;
+        } else {
+            out.push_str(token);
+        }
+
+        if token == "\"" {
+            quote_open = !quote_open;
+        }
+        glue = attaches_right;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sentences_are_marked_and_punctuation_split() {
+        assert_eq!(
+            tokenize("Wait... don't go--now"),
+            [START, "wait", "...", START, "don't", "go", "--", "now"]
+        );
+        assert_eq!(
+            tokenize("She said \"Go.\" Then I left"),
+            [START, "she", "said", "\"", "Go", ".", "\"", START, "then", "I", "left"]
+        );
+    }
+
+    #[test]
+    fn detokenize_spaces_and_capitalizes() {
+        let tokens = tokenize("She said \"Go.\" Then I left");
+        assert_eq!(detokenize("", &tokens), "She said \"Go.\" Then I left");
+
+        let tokens: Vec<String> = ["the", "bells", ",", "then", "silence", ".", START, "nobody"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(detokenize("It rang (", &tokens), "the bells, then silence. Nobody");
+        assert_eq!(detokenize("It rang", &tokens), " the bells, then silence. Nobody");
+    }
+}
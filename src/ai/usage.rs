@@ -0,0 +1,230 @@
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::TokenUsage;
+use crate::config::{config_dir, AiProvider};
+
+const USAGE_FILE: &str = "usage.jsonl";
+
+/// One API call as stored in the usage history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub timestamp: DateTime<Utc>,
+    pub document: Option<String>,
+    pub provider: AiProvider,
+    pub model: String,
+    pub usage: TokenUsage,
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsageTotals {
+    pub requests: u32,
+    pub tokens: u64,
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        self.tokens += record.usage.total();
+        self.cost += record.cost;
+    }
+
+    /// Compact form for the status bar, e.g. "$0.0042 / 1.3k tok"
+    pub fn summary(&self) -> String {
+        let tokens = if self.tokens >= 1000 {
+            format!("{:.1}k", self.tokens as f64 / 1000.0)
+        } else {
+            self.tokens.to_string()
+        };
+        format!("${:.4} / {} tok", self.cost, tokens)
+    }
+}
+
+/// Stable identifier for a document in the on-disk histories
+pub fn document_key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn same_month(a: DateTime<Utc>, b: DateTime<Utc>) -> bool {
+    a.year() == b.year() && a.month() == b.month()
+}
+
+/// Running token and cost totals for this session, the current calendar
+/// month and the open document, backed by an append-only JSONL history in
+/// the config directory
+pub struct UsageTracker {
+    session: UsageTotals,
+    month: UsageTotals,
+    month_start: DateTime<Utc>,
+    document: UsageTotals,
+    document_key: Option<String>,
+    path: Option<PathBuf>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        let mut tracker = Self {
+            session: UsageTotals::default(),
+            month: UsageTotals::default(),
+            month_start: Utc::now(),
+            document: UsageTotals::default(),
+            document_key: None,
+            path: config_dir().map(|dir| dir.join(USAGE_FILE)),
+        };
+        for record in tracker.history() {
+            if same_month(record.timestamp, tracker.month_start) {
+                tracker.month.add(&record);
+            }
+        }
+        tracker
+    }
+
+    pub fn session(&self) -> UsageTotals {
+        self.session
+    }
+
+    /// Totals for the current calendar month (UTC), including earlier sessions.
+    /// Nothing may have been recorded since the month turned over, as when
+    /// the budget blocked every request, so the date is checked here too.
+    pub fn month(&self) -> UsageTotals {
+        if same_month(self.month_start, Utc::now()) {
+            self.month
+        } else {
+            UsageTotals::default()
+        }
+    }
+
+    pub fn document(&self) -> UsageTotals {
+        self.document
+    }
+
+    /// Switch to a document, totalling everything spent on it in earlier sessions
+    pub fn set_document(&mut self, path: Option<&Path>) {
+        self.document_key = path.map(document_key);
+        self.document = UsageTotals::default();
+
+        if let Some(key) = &self.document_key {
+            for record in self.history() {
+                if record.document.as_ref() == Some(key) {
+                    self.document.add(&record);
+                }
+            }
+        }
+    }
+
+    pub fn record(&mut self, provider: AiProvider, model: &str, usage: TokenUsage, cost: f64) {
+        let record = UsageRecord {
+            timestamp: Utc::now(),
+            document: self.document_key.clone(),
+            provider,
+            model: model.to_string(),
+            usage,
+            cost,
+        };
+        if !same_month(record.timestamp, self.month_start) {
+            self.month = UsageTotals::default();
+            self.month_start = record.timestamp;
+        }
+        self.session.add(&record);
+        self.month.add(&record);
+        self.document.add(&record);
+        self.append(&record);
+    }
+
+    pub fn history(&self) -> Vec<UsageRecord> {
+        let Some(path) = &self.path else { return Vec::new() };
+        fs::read_to_string(path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn append(&self, record: &UsageRecord) {
+        let Some(path) = &self.path else { return };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let (Ok(mut file), Ok(line)) = (
+            OpenOptions::new().create(true).append(true).open(path),
+            serde_json::to_string(record),
+        ) {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+    use crate::config::Config;
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> TokenUsage {
+        TokenUsage { input_tokens, output_tokens }
+    }
+
+    #[test]
+    fn requests_are_priced_from_the_table() {
+        let config = Config::default();
+        let cost = config.estimate_cost("gpt-4o-mini", usage(1_000_000, 1_000_000));
+        assert!((cost - 0.75).abs() < 1e-9, "{}", cost);
+        assert_eq!(config.estimate_cost("unlisted-model", usage(500, 500)), 0.0);
+    }
+
+    #[test]
+    fn totals_add_up_and_survive_a_restart() {
+        let dir = TestConfigDir::new();
+        let document = dir.path().join("novel.md");
+        let mut tracker = UsageTracker::new();
+        tracker.record(AiProvider::OpenAI, "gpt-4o", usage(900, 200), 0.01);
+        tracker.set_document(Some(&document));
+        tracker.record(AiProvider::Anthropic, "claude", usage(100, 50), 0.02);
+
+        let session = tracker.session();
+        assert_eq!((session.requests, session.tokens), (2, 1250));
+        assert!((session.cost - 0.03).abs() < 1e-9);
+        assert_eq!(session.summary(), "$0.0300 / 1.2k tok");
+        assert_eq!(tracker.document().requests, 1);
+
+        let mut restarted = UsageTracker::new();
+        assert_eq!(restarted.session().requests, 0);
+        assert_eq!(restarted.month().requests, 2);
+        restarted.set_document(Some(&document));
+        assert_eq!(restarted.document().tokens, 150);
+        assert_eq!(restarted.history()[1].model, "claude");
+    }
+
+    #[test]
+    fn a_new_month_starts_from_zero_without_a_request() {
+        let _dir = TestConfigDir::new();
+        let mut tracker = UsageTracker::new();
+        tracker.record(AiProvider::OpenAI, "gpt-4o", usage(900, 200), 5.0);
+        assert_eq!(tracker.month().requests, 1);
+
+        tracker.month_start = Utc::now().checked_sub_months(chrono::Months::new(1)).unwrap();
+        assert_eq!(tracker.month().requests, 0);
+        assert_eq!(tracker.month().cost, 0.0);
+        assert_eq!(tracker.session().requests, 1);
+
+        tracker.record(AiProvider::OpenAI, "gpt-4o", usage(10, 10), 0.5);
+        assert_eq!((tracker.month().requests, tracker.month().cost), (1, 0.5));
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const DICTIONARY: &str = include_str!("../../data/dictionary.txt");
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+struct Entry {
+    /// Weighted occurrences; 0 for words only known from a dictionary
+    count: u32,
+    /// Learning batch the word was last seen in
+    seen: u64,
+    /// In a dictionary, so known even when no text uses it
+    #[serde(skip)]
+    listed: bool,
+}
+
+/// Every word the engine knows, sorted so a prefix of any length finds its
+/// completions with one range scan
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Vocabulary {
+    words: BTreeMap<String, Entry>,
+    /// Advanced once per learning batch, so words from the latest edits
+    /// count as the most recent
+    clock: u64,
+}
+
+/// A word as it should be indexed: lowercased, without surrounding
+/// punctuation. Apostrophes and hyphens inside the word are kept.
+pub fn normalize(word: &str) -> Option<String> {
+    let trimmed = word.trim_matches(|c: char| !c.is_alphanumeric());
+    if trimmed.chars().any(char::is_alphabetic) {
+        Some(trimmed.to_lowercase())
+    } else {
+        None
+    }
+}
+
+impl Vocabulary {
+    /// Start a new learning batch
+    pub fn tick(&mut self) {
+        self.clock += 1;
+    }
+
+    pub fn add(&mut self, word: &str, weight: u32) {
+        let Some(word) = normalize(word) else { return };
+        let entry = self.words.entry(word).or_default();
+        entry.count += weight;
+        entry.seen = self.clock;
+    }
+
+    /// Forget occurrences of a word, and the word itself once nothing is
+    /// left of it unless a dictionary lists it. Half-typed words learned
+    /// while the author paused go this way.
+    pub fn remove(&mut self, word: &str, weight: u32) {
+        let Some(word) = normalize(word) else { return };
+        if let Some(entry) = self.words.get_mut(&word) {
+            entry.count = entry.count.saturating_sub(weight);
+            if entry.count == 0 && !entry.listed {
+                self.words.remove(&word);
+            }
+        }
+    }
+
+    /// Add the built-in word list, and any other, without counting anything
+    pub fn add_dictionary(&mut self, words: Option<&str>) {
+        for word in DICTIONARY.lines().chain(words.into_iter().flat_map(str::lines)) {
+            if let Some(word) = normalize(word) {
+                self.words.entry(word).or_default().listed = true;
+            }
+        }
+    }
+
+    /// Words longer than `prefix` that start with it, best first. Words seen
+    /// often, and in recent batches, rank highest; dictionary-only words
+    /// follow, shortest first. Returns each word with whether it was seen.
+    pub fn complete(&self, prefix: &str, limit: usize) -> Vec<(&str, bool)> {
+        let prefix = prefix.to_lowercase();
+        let mut matches: Vec<(&str, f32)> = self
+            .words
+            .range(prefix.clone()..)
+            .take_while(|(word, _)| word.starts_with(&prefix))
+            .filter(|(word, _)| word.len() > prefix.len())
+            .map(|(word, entry)| (word.as_str(), self.score(entry)))
+            .collect();
+
+        matches.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.len().cmp(&b.0.len())));
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(word, score)| (word, score > 0.0))
+            .collect()
+    }
+
+    /// Log frequency, boosted up to twice over for words from the latest batch
+    fn score(&self, entry: &Entry) -> f32 {
+        let age = self.clock.saturating_sub(entry.seen) as f32;
+        (entry.count as f32).ln_1p() * (1.0 + 1.0 / (1.0 + age))
+    }
+}
+
+/// Extend `typed` with the rest of `word`, in capitals if the user is
+/// typing in capitals
+pub fn complete_as_typed(typed: &str, word: &str) -> Option<String> {
+    let suffix = word.get(typed.to_lowercase().len()..)?;
+    let shouting = typed.chars().filter(|c| c.is_alphabetic()).count() > 1
+        && typed.chars().all(|c| !c.is_lowercase());
+    Some(if shouting { suffix.to_uppercase() } else { suffix.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_complete_from_any_prefix_as_typed() {
+        let mut vocabulary = Vocabulary::default();
+        vocabulary.add_dictionary(None);
+        vocabulary.add("beauty", 1);
+
+        // Seen words first, then the dictionary's
+        assert_eq!(vocabulary.complete("beaut", 3)[0], ("beauty", true));
+        assert_eq!(vocabulary.complete("Beauti", 3)[0], ("beautiful", false));
+        assert_eq!(complete_as_typed("Beauti", "beautiful").as_deref(), Some("ful"));
+        assert_eq!(complete_as_typed("BEAUTI", "beautiful").as_deref(), Some("FUL"));
+
+        // The latest batch outranks older words seen as often
+        vocabulary.tick();
+        vocabulary.add("Beaumont", 1);
+        assert_eq!(vocabulary.complete("beau", 3)[0], ("beaumont", true));
+        vocabulary.remove("Beaumont", 1);
+        assert!(vocabulary.complete("beaum", 3).is_empty());
+    }
+}
@@ -1,17 +1,21 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use std::fs;
 use std::path::PathBuf;
 use tokio::sync::mpsc;
 
 use crate::buffer::TextBuffer;
-use crate::ai::{ApiResponse, HybridEngine, OpenAIClient, AnthropicClient, Suggestion};
+use crate::ai::{available_models, chat_system_prompt, parse_annotations, split_chapters, ApiResponse, AuditLog, Sampling, AuditViewer, CacheKey, ChatMessage, ChatPanel, ChatRole, ChapterSummary, CompletionCache, Critique, HybridEngine, OpenAIClient, AnthropicClient, Outcome, RedactionMap, Redactor, Slot, StyleProfile, Suggestion, SuggestionAnalytics, SummaryStore, UsageTracker};
 use crate::config::{AiProvider, AiMode};
-use crate::commands::{CommandParser, Generators};
+use crate::commands::{pieces, Cast, CastKind, Command, CommandMenu, CommandParser, CommandRegistry, Expansion, Generators, Invocation, Piece};
 use crate::config::Config;
 use crate::emoji::EmojiEngine;
 use crate::search::{SearchState, SearchMode};
 use crate::ui::synonyms::get_synonyms;
 
 const MAX_UNDO_HISTORY: usize = 100;
+/// Ticks without an edit before the local engine learns the document (~2s)
+const IDLE_TICKS: u64 = 40;
 
 #[derive(Clone)]
 struct EditorState {
@@ -26,6 +30,10 @@ pub struct App {
     pub sentence_suggestion: Option<Suggestion>,
     pub api_suggestion: Option<Suggestion>,
     pub command_preview: Option<String>,
+    pub command_menu: Option<CommandMenu>,
+    /// The text before the cursor the menu was last opened for, so a
+    /// selection or dismissal lasts until the text changes
+    command_menu_context: String,
     pub pulse_phase: f32,
     pub show_synonyms: bool,
     pub synonyms: Vec<String>,
@@ -33,15 +41,27 @@ pub struct App {
     pub synonym_word: Option<String>,
     pub synonym_range: Option<(usize, usize)>,
     pub show_help: bool,
+    pub show_stats: bool,
+    pub show_cast: bool,
     pub file_path: Option<PathBuf>,
     pub emoji_mode: bool,
     pub scroll_offset: usize,
     pub wrap_width: usize,
+    visible_height: usize,
     pub api_loading: bool,
     pub status_message: Option<String>,
+    pub usage: UsageTracker,
     ai: HybridEngine,
+    cache: CompletionCache,
+    redactor: Redactor,
+    pending_cache_key: Option<CacheKey>,
     emoji: EmojiEngine,
+    commands: CommandRegistry,
+    /// The one source of randomness for suggestions and generators, seeded
+    /// from `seed` in the config for reproducible runs
+    rng: StdRng,
     tick_count: u64,
+    last_edit_tick: u64,
     api_tx: mpsc::Sender<ApiResponse>,
     undo_stack: Vec<EditorState>,
     redo_stack: Vec<EditorState>,
@@ -49,20 +69,45 @@ pub struct App {
     pub clipboard: String,
     // Search
     pub search: SearchState,
+    // Audit log viewer
+    pub audit_viewer: AuditViewer,
+    // Chat side panel
+    pub chat: ChatPanel,
+    // Critique annotations
+    pub critique: Critique,
+    // Chapter summaries, the long-range memory of the context builder
+    pub summaries: SummaryStore,
+    // What becomes of shown suggestions
+    pub analytics: SuggestionAnalytics,
+    // Everyone and everything generated for this document
+    pub cast: Cast,
 }
 
 impl App {
-    pub fn new(api_tx: mpsc::Sender<ApiResponse>) -> Self {
-        let config = Config::default();
+    pub fn new(config: Config, api_tx: mpsc::Sender<ApiResponse>) -> Self {
+        let status_message = config
+            .load_error
+            .as_ref()
+            .map(|e| format!("{} - offline until fixed", e));
         Self {
             buffer: TextBuffer::new(),
             ai: HybridEngine::new(config.clone()),
+            cache: CompletionCache::new(&config),
+            redactor: Redactor::new(&config.redaction),
+            pending_cache_key: None,
             emoji: EmojiEngine::new(),
+            commands: CommandRegistry::load(),
+            rng: match config.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
             config,
             current_suggestion: None,
             sentence_suggestion: None,
             api_suggestion: None,
             command_preview: None,
+            command_menu: None,
+            command_menu_context: String::new(),
             pulse_phase: 0.0,
             show_synonyms: false,
             synonyms: Vec::new(),
@@ -70,18 +115,29 @@ impl App {
             synonym_word: None,
             synonym_range: None,
             show_help: false,
+            show_stats: false,
+            show_cast: false,
             file_path: None,
             emoji_mode: false,
             scroll_offset: 0,
             wrap_width: 80,
+            visible_height: 24,
             api_loading: false,
-            status_message: None,
+            status_message,
+            usage: UsageTracker::new(),
             tick_count: 0,
+            last_edit_tick: 0,
             api_tx,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             clipboard: String::new(),
             search: SearchState::new(),
+            audit_viewer: AuditViewer::default(),
+            chat: ChatPanel::default(),
+            critique: Critique::default(),
+            summaries: SummaryStore::default(),
+            analytics: SuggestionAnalytics::new(),
+            cast: Cast::default(),
         }
     }
 
@@ -91,6 +147,7 @@ impl App {
             cursor: self.buffer.cursor(),
         };
         self.undo_stack.push(state);
+        self.last_edit_tick = self.tick_count;
         if self.undo_stack.len() > MAX_UNDO_HISTORY {
             self.undo_stack.remove(0);
         }
@@ -105,6 +162,7 @@ impl App {
             };
             self.redo_stack.push(current);
             self.buffer = state.buffer;
+            self.last_edit_tick = self.tick_count;
             self.update_suggestions();
         }
     }
@@ -117,6 +175,7 @@ impl App {
             };
             self.undo_stack.push(current);
             self.buffer = state.buffer;
+            self.last_edit_tick = self.tick_count;
             self.update_suggestions();
         }
     }
@@ -137,12 +196,25 @@ impl App {
             self.update_suggestions();
         }
 
+        if self.tick_count.is_multiple_of(100) {
+            self.analytics.save();
+        }
+
+        // Learn from the document once the author pauses
+        if self.tick_count - self.last_edit_tick == IDLE_TICKS {
+            self.learn_document();
+        }
+
         // Auto-fetch API suggestions when local confidence is low (every ~5 seconds)
-        if self.tick_count % 100 == 0 && self.config.has_api_key() && !self.api_loading {
+        if self.tick_count % 100 == 0
+            && self.config.has_api_key()
+            && !self.api_loading
+            && self.api_block_reason().is_none()
+        {
             let context = self.buffer.text_before_cursor();
             if context.len() > 30 {
                 if let Some(suggestion) = &self.current_suggestion {
-                    if suggestion.confidence < 0.4 {
+                    if self.calibrated(suggestion) < 0.4 {
                         self.fetch_api_suggestion();
                     }
                 }
@@ -154,38 +226,114 @@ impl App {
         let context = self.buffer.text_before_cursor();
 
         // Check for slash commands first
-        if let Some(partial) = CommandParser::is_partial_command(&context) {
-            // Check for emoji commands
-            if partial.starts_with("emoji") || partial == "e" {
-                self.command_preview = Some("emoji categories: face, nature, animal, object, food, heart".to_string());
-            } else {
-                self.command_preview = Some(format!("/{} (press Enter)", partial));
-            }
+        if context != self.command_menu_context {
+            self.command_menu = CommandMenu::open(&self.commands, &context);
+            self.command_menu_context = context.clone();
+        }
+        if self.command_menu.is_some() {
+            self.command_preview = None;
             self.current_suggestion = None;
+            self.track_suggestions();
             return;
         }
 
+        // A command with arguments shows what it will make, or what's wrong
+        if let Some((parsed, start, end)) = self.command_at_cursor(&context) {
+            if let Ok(Invocation { command: Command::Cast(query), .. }) = &parsed {
+                self.complete_cast(query.as_deref().unwrap_or_default(), context.ends_with(' '));
+                return;
+            }
+            let preview = match parsed {
+                Ok(invocation) if context[start..end].contains(' ') => {
+                    let what = Generators::preview(&invocation.command);
+                    match invocation.count {
+                        1 => Some(format!("{} (press Enter)", what)),
+                        n => Some(format!("{} \u{00D7} {} (press Enter)", n, what)),
+                    }
+                }
+                Ok(_) => None,
+                Err(message) => Some(message),
+            };
+            if preview.is_some() {
+                self.command_preview = preview;
+                self.current_suggestion = None;
+                self.track_suggestions();
+                return;
+            }
+        }
+
         self.command_preview = None;
 
-        // Get local suggestions
-        let suggestions = self.ai.suggest_local(&context);
+        // Get local suggestions, best calibrated confidence first
+        let mut suggestions = self.ai.suggest_local(&context, self.sampling(), &mut self.rng);
+        suggestions.sort_by(|a, b| self.calibrated(b).total_cmp(&self.calibrated(a)));
         self.current_suggestion = suggestions.into_iter().next();
 
         // Add emoji suggestion if emoji mode is on
         if self.emoji_mode {
-            if let Some(emoji) = self.emoji.suggest_emoji(&context) {
+            if let Some(emoji) = self.emoji.suggest_emoji(&context, &mut self.rng) {
                 self.current_suggestion = Some(Suggestion {
                     text: format!(" {}", emoji),
                     confidence: 0.7,
                     source: crate::ai::SuggestionSource::Local,
+                    cached: false,
                 });
             }
         }
 
         // Occasionally generate sentence suggestion
         if self.tick_count % 40 == 0 && context.len() > 20 {
-            self.sentence_suggestion = self.ai.suggest_sentence_local(&context);
+            self.sentence_suggestion = self.ai.suggest_sentence_local(&context, self.sampling(), &mut self.rng);
         }
+
+        self.track_suggestions();
+    }
+
+    /// List the cast members `/cast` could mean, and offer the rest of the
+    /// first one's name as the inline suggestion
+    fn complete_cast(&mut self, query: &str, after_space: bool) {
+        let matches = self.cast.matches(query);
+        self.command_preview = Some(if matches.is_empty() {
+            format!("nobody in the cast is called '{}'", query)
+        } else {
+            let names: Vec<String> = matches
+                .iter()
+                .map(|m| match &m.role {
+                    Some(role) => format!("{} ({})", m.name, role),
+                    None => m.name.clone(),
+                })
+                .collect();
+            format!("cast: {}", names.join(", "))
+        });
+        self.current_suggestion = matches.first().map(|member| Suggestion {
+            text: format!(
+                "{}{}",
+                if query.is_empty() && !after_space { " " } else { "" },
+                member.name.chars().skip(query.chars().count()).collect::<String>()
+            ),
+            confidence: 1.0,
+            source: crate::ai::SuggestionSource::Local,
+            cached: false,
+        });
+        self.track_suggestions();
+    }
+
+    fn learn_document(&mut self) {
+        self.ai.learn_document(&self.buffer.to_string());
+    }
+
+    /// Tell analytics which suggestions are on screen now
+    fn track_suggestions(&mut self) {
+        let context = self.buffer.text_before_cursor();
+        let provider = self.config.ai_provider;
+        let inline = self.api_suggestion.as_ref().or(self.current_suggestion.as_ref());
+        self.analytics.observe(Slot::Inline, inline, provider, &context);
+        self.analytics.observe(Slot::Sentence, self.sentence_suggestion.as_ref(), provider, &context);
+    }
+
+    /// A suggestion's confidence, corrected by how often its kind gets accepted
+    fn calibrated(&self, suggestion: &Suggestion) -> f32 {
+        self.analytics.calibrate(suggestion.source, self.config.ai_provider, suggestion.confidence)
     }
 
     pub fn insert_char(&mut self, c: char) {
@@ -200,39 +348,165 @@ impl App {
             return;
         }
 
+        // Take the command picked in the menu; those that need an argument
+        // wait for it, the rest run at once
+        if self.command_menu.is_some() && self.complete_command() {
+            return;
+        }
+
         // Check for command execution
         let context = self.buffer.text_before_cursor();
-        if let Some((cmd, start, end)) = CommandParser::parse(&context) {
-            self.save_state();
+        match self.command_at_cursor(&context) {
+            Some((Ok(invocation), start, end)) => {
+                let generated = match self.run(&invocation, &mut Expansion::default()) {
+                    Ok(generated) => generated,
+                    Err(message) => {
+                        self.command_preview = Some(message);
+                        return;
+                    }
+                };
+                self.save_state();
 
-            // Handle emoji commands separately
-            let generated = match &cmd {
-                crate::commands::Command::Emoji(Some(category)) => {
-                    self.emoji.emoji_by_category(category).unwrap_or("\u{2728}").to_string()
-                }
-                crate::commands::Command::Emoji(None) => {
-                    self.emoji.random_emoji().to_string()
-                }
-                _ => Generators::generate(&cmd),
-            };
+                // Replace command with generated text
+                self.replace_before_cursor(start, end, &generated);
+                self.command_preview = None;
+                self.update_suggestions();
+            }
+            // Left as typed, so the mistake can be fixed
+            Some((Err(message), _, _)) => self.command_preview = Some(message),
+            None => {
+                self.save_state();
+                self.buffer.insert_newline();
+                self.update_suggestions();
+            }
+        }
+    }
 
-            // Replace command with generated text
-            let current_line = self.buffer.cursor().1;
-            let line_start = self.buffer.lines()[..current_line]
-                .iter()
-                .map(|l| l.len() + 1)
-                .sum::<usize>();
-            let rel_start = start.saturating_sub(line_start);
-            let rel_end = end.saturating_sub(line_start);
+    /// The slash command that ends at the cursor, if any; one followed by
+    /// more prose is just text now
+    fn command_at_cursor(&self, context: &str) -> Option<(Result<Invocation, String>, usize, usize)> {
+        CommandParser::parse(&self.commands, context).filter(|(_, _, end)| *end == context.trim_end().len())
+    }
 
-            self.buffer.replace_word(rel_start, rel_end, &generated);
-            self.command_preview = None;
-            self.update_suggestions();
+    /// Replace the menu's half-typed name with the selected command.
+    /// Returns whether it waits for an argument, after a space.
+    fn complete_command(&mut self) -> bool {
+        let Some(menu) = self.command_menu.take() else { return false };
+        let item = menu.selected();
+        let waits = matches!(item.command, Command::Template(None) | Command::Cast(None));
+        let name = format!("/{}{}", item.name, if waits { " " } else { "" });
+        self.save_state();
+        let end = self.buffer.text_before_cursor().len();
+        self.replace_before_cursor(menu.start, end, &name);
+        self.update_suggestions();
+        waits
+    }
+
+    /// Replace `start..end` of the text before the cursor, both on the
+    /// cursor's line, with `text`, which may span lines
+    fn replace_before_cursor(&mut self, start: usize, end: usize, text: &str) {
+        let current_line = self.buffer.cursor().1;
+        let line_start = self.buffer.lines()[..current_line]
+            .iter()
+            .map(|l| l.len() + 1)
+            .sum::<usize>();
+        let rel_start = start.saturating_sub(line_start);
+        let rel_end = end.saturating_sub(line_start);
+
+        self.buffer.replace_word(rel_start, rel_end, "");
+        self.buffer.insert_str(text);
+    }
+
+    pub fn command_menu_up(&mut self) {
+        if let Some(menu) = &mut self.command_menu {
+            menu.up();
+        }
+    }
+
+    pub fn command_menu_down(&mut self) {
+        if let Some(menu) = &mut self.command_menu {
+            menu.down();
+        }
+    }
+
+    /// Generate what an invocation asks for, as many times as it asks
+    fn run(&mut self, invocation: &Invocation, expansion: &mut Expansion) -> Result<String, String> {
+        // Several values are kept distinct where the pool allows
+        let mut values: Vec<String> = Vec::new();
+        for attempt in 0..invocation.count * 5 {
+            let value = self.generate(&invocation.command, expansion)?;
+            let retries_left = invocation.count * 5 - attempt > invocation.count - values.len();
+            if !values.contains(&value) || !retries_left {
+                values.push(value);
+            }
+            if values.len() == invocation.count {
+                break;
+            }
+        }
+
+        // Generated names join the cast; `/cast … --role=…` recasts someone
+        let kind = invocation.command.cast_kind();
+        if kind.is_some() || invocation.role.is_some() {
+            for value in &values {
+                self.cast.record(value, kind.unwrap_or(CastKind::Character), invocation.role.as_deref());
+            }
+        }
+        Ok(invocation.join(&values))
+    }
+
+    fn generate(&mut self, command: &Command, expansion: &mut Expansion) -> Result<String, String> {
+        // Handle emoji commands separately
+        let value = match command {
+            Command::Emoji(Some(category)) => {
+                self.emoji.emoji_by_category(category, &mut self.rng).unwrap_or("\u{2728}").to_string()
+            }
+            Command::Emoji(None) => self.emoji.random_emoji(&mut self.rng).to_string(),
+            Command::Cast(query) => {
+                return self.cast.find(query.as_deref().unwrap_or_default()).map(|member| member.name.clone());
+            }
+            Command::Template(name) => {
+                let text = name
+                    .as_deref()
+                    .and_then(|name| self.commands.template(name))
+                    .map(|template| template.text.clone())
+                    .unwrap_or_default();
+                return self.expand(&text, expansion);
+            }
+            _ => Generators::generate(command, &mut self.rng),
+        };
+        // List entries may refer to other generators in turn
+        if value.contains('{') {
+            self.expand(&value, expansion)
         } else {
-            self.save_state();
-            self.buffer.insert_newline();
-            self.update_suggestions();
+            Ok(value)
+        }
+    }
+
+    /// Replace each `{…}` in `text`, reusing what the expansion already
+    /// generated for the same reference or label
+    fn expand(&mut self, text: &str, expansion: &mut Expansion) -> Result<String, String> {
+        expansion.enter()?;
+        let mut out = String::new();
+        for piece in pieces(text)? {
+            match piece {
+                Piece::Text(text) => out.push_str(text),
+                Piece::Recall(label) => out.push_str(expansion.recall(label)?),
+                Piece::Reference(reference, command) => {
+                    let value = match expansion.get(reference) {
+                        Some(value) => value.clone(),
+                        None => {
+                            let invocation = CommandParser::parse_reference(&self.commands, command)?;
+                            let value = self.run(&invocation, expansion)?;
+                            expansion.remember(reference, &value);
+                            value
+                        }
+                    };
+                    out.push_str(&value);
+                }
+            }
         }
+        expansion.leave();
+        Ok(out)
     }
 
     pub fn handle_backspace(&mut self) {
@@ -487,6 +761,15 @@ impl App {
     }
 
     pub fn accept_suggestion(&mut self) {
+        if self.command_menu.is_some() {
+            self.complete_command();
+            return;
+        }
+
+        if self.api_suggestion.is_some() || self.current_suggestion.is_some() {
+            self.analytics.accept(Slot::Inline, Outcome::Accepted);
+        }
+
         // Prefer API suggestion if available, otherwise local
         if let Some(suggestion) = self.api_suggestion.take() {
             self.save_state();
@@ -499,8 +782,28 @@ impl App {
         }
     }
 
+    /// Take only the first word of the inline suggestion
+    pub fn accept_next_word(&mut self) {
+        let Some(suggestion) = self.api_suggestion.take().or_else(|| self.current_suggestion.take()) else {
+            self.move_cursor_right();
+            return;
+        };
+        self.analytics.accept(Slot::Inline, Outcome::Partial);
+
+        let text = suggestion.text.as_str();
+        let leading = text.len() - text.trim_start().len();
+        let word_end = text[leading..]
+            .find(char::is_whitespace)
+            .map(|i| leading + i)
+            .unwrap_or(text.len());
+        self.save_state();
+        self.buffer.insert_str(&text[..word_end]);
+        self.update_suggestions();
+    }
+
     pub fn accept_sentence_suggestion(&mut self) {
         if let Some(suggestion) = self.sentence_suggestion.take() {
+            self.analytics.accept(Slot::Sentence, Outcome::Accepted);
             self.save_state();
             self.buffer.insert_str(&suggestion.text);
             self.update_suggestions();
@@ -513,11 +816,18 @@ impl App {
             self.synonyms.clear();
         } else if self.show_help {
             self.show_help = false;
+        } else if self.show_stats {
+            self.show_stats = false;
+        } else if self.show_cast {
+            self.show_cast = false;
+        } else if self.command_menu.is_some() {
+            self.command_menu = None;
         } else {
             self.current_suggestion = None;
             self.sentence_suggestion = None;
             self.api_suggestion = None;
             self.command_preview = None;
+            self.track_suggestions();
         }
     }
 
@@ -570,6 +880,201 @@ impl App {
         self.show_help = !self.show_help;
     }
 
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+        if self.show_stats {
+            self.analytics.save();
+        }
+    }
+
+    pub fn toggle_cast(&mut self) {
+        self.show_cast = !self.show_cast;
+    }
+
+    pub fn toggle_audit_viewer(&mut self) {
+        if self.audit_viewer.is_active {
+            self.audit_viewer.close();
+        } else {
+            self.audit_viewer.open(self.file_path.as_deref());
+            if !self.config.audit_log {
+                self.status_message = Some("Audit log is off - set audit_log in config.json".to_string());
+            }
+        }
+    }
+
+    pub fn toggle_chat(&mut self) {
+        self.chat.toggle();
+    }
+
+    /// Send the typed question, with the selection or current chapter as context
+    pub fn send_chat_message(&mut self) {
+        if self.chat.loading {
+            return;
+        }
+
+        if self.config.ai_provider == AiProvider::Local {
+            self.status_message = Some("Chat needs an API provider - Ctrl+P to switch".to_string());
+            return;
+        }
+
+        if let Some(reason) = self.api_block_reason() {
+            self.status_message = Some(format!("{} - chat unavailable", reason));
+            return;
+        }
+
+        if !self.config.has_api_key() {
+            let provider = self.config.ai_provider;
+            self.status_message = Some(format!("No {} API key - set env var", provider));
+            return;
+        }
+
+        let Some(question) = self.chat.take_input() else { return };
+        self.chat.push(ChatRole::User, question);
+
+        let (label, context) = match self.buffer.get_selection() {
+            Some(selection) => ("Selected passage", selection),
+            None => ("Current chapter", self.buffer.chapter_at_cursor()),
+        };
+
+        // The passage and every turn share one set of placeholders, so a name
+        // masked in the question matches the same name in the passage
+        let mut redactions = RedactionMap::default();
+        let system = chat_system_prompt(label, &self.redactor.redact_with(&context, &mut redactions));
+        let messages: Vec<ChatMessage> = self.chat.messages
+            .iter()
+            .map(|m| ChatMessage {
+                role: m.role,
+                content: self.redactor.redact_with(&m.content, &mut redactions),
+            })
+            .collect();
+
+        self.chat.loading = true;
+        let config = self.config.clone();
+        let audit = AuditLog::new(&config, self.file_path.as_deref());
+        let tx = self.api_tx.clone();
+
+        tokio::spawn(async move {
+            let provider = config.ai_provider;
+            let (model, result) = match provider {
+                AiProvider::Local => (String::new(), None), // Handled above
+                AiProvider::OpenAI => {
+                    let model = config.openai_model.clone();
+                    let client = OpenAIClient::new(config).with_audit(audit);
+                    (model, client.chat(&system, &messages).await)
+                }
+                AiProvider::Anthropic => {
+                    let client = AnthropicClient::new(config).with_audit(audit);
+                    (client.model_name().to_string(), client.chat(&system, &messages).await)
+                }
+            };
+
+            let reply = match result {
+                Some(completion) => {
+                    let usage = completion.usage;
+                    let _ = tx.send(ApiResponse::Usage { provider, model, usage }).await;
+                    Some(redactions.restore(&completion.text))
+                }
+                None => None,
+            };
+            let _ = tx.send(ApiResponse::ChatReply(reply)).await;
+        });
+    }
+
+    /// Insert the latest chat answer at the cursor
+    pub fn insert_chat_reply(&mut self) {
+        let Some(reply) = self.chat.last_reply().map(String::from) else {
+            self.status_message = Some("No chat reply to insert".to_string());
+            return;
+        };
+        self.save_state();
+        self.buffer.insert_str(&reply);
+        self.update_suggestions();
+        self.status_message = Some("Chat reply inserted".to_string());
+    }
+
+    /// Clear the critique if one is showing, otherwise analyze the text on screen
+    pub fn toggle_critique(&mut self) {
+        if !self.critique.is_empty() {
+            self.critique.clear();
+            self.status_message = Some("Critique cleared".to_string());
+            return;
+        }
+
+        if self.critique.loading {
+            return;
+        }
+
+        if self.config.ai_provider == AiProvider::Local {
+            self.status_message = Some("Analysis needs an API provider - Ctrl+P to switch".to_string());
+            return;
+        }
+
+        if let Some(reason) = self.api_block_reason() {
+            self.status_message = Some(format!("{} - analysis unavailable", reason));
+            return;
+        }
+
+        if !self.config.has_api_key() {
+            let provider = self.config.ai_provider;
+            self.status_message = Some(format!("No {} API key - set env var", provider));
+            return;
+        }
+
+        let (first, last) = self.visible_line_range();
+        let text = self.buffer.lines()[first..=last].join("\n");
+        if text.trim().is_empty() {
+            self.status_message = Some("Nothing on screen to analyze".to_string());
+            return;
+        }
+
+        let (redacted, redactions) = self.redactor.redact(&text);
+
+        self.critique.loading = true;
+        self.status_message = Some(format!("Analyzing with {}...", self.config.current_model_display()));
+        let config = self.config.clone();
+        let audit = AuditLog::new(&config, self.file_path.as_deref());
+        let tx = self.api_tx.clone();
+
+        tokio::spawn(async move {
+            let provider = config.ai_provider;
+            let (model, result) = match provider {
+                AiProvider::Local => (String::new(), None), // Handled above
+                AiProvider::OpenAI => {
+                    let model = config.openai_model.clone();
+                    let client = OpenAIClient::new(config).with_audit(audit);
+                    (model, client.critique(&redacted).await)
+                }
+                AiProvider::Anthropic => {
+                    let client = AnthropicClient::new(config).with_audit(audit);
+                    (client.model_name().to_string(), client.critique(&redacted).await)
+                }
+            };
+
+            let annotations = match result {
+                Some(completion) => {
+                    let usage = completion.usage;
+                    let _ = tx.send(ApiResponse::Usage { provider, model, usage }).await;
+                    parse_annotations(&completion.text, &text, first, &redactions)
+                }
+                None => None,
+            };
+            let _ = tx.send(ApiResponse::Critique(annotations)).await;
+        });
+    }
+
+    /// First and last buffer lines that are at least partly on screen
+    fn visible_line_range(&self) -> (usize, usize) {
+        let wrapped = self.buffer.get_wrapped_lines(self.wrap_width);
+        let mut visible = wrapped
+            .iter()
+            .skip(self.scroll_offset)
+            .take(self.visible_height.max(1))
+            .map(|(line, _)| *line);
+        let first = visible.next().unwrap_or(0);
+        let last = visible.next_back().unwrap_or(first);
+        (first, last)
+    }
+
     pub fn toggle_emoji_mode(&mut self) {
         self.emoji_mode = !self.emoji_mode;
         self.status_message = Some(if self.emoji_mode {
@@ -580,7 +1085,17 @@ impl App {
         self.update_suggestions();
     }
 
+    /// Ask the API for a few words to continue the text
     pub fn fetch_api_suggestion(&mut self) {
+        self.request_api_suggestion(false);
+    }
+
+    /// Ask the API for a whole sentence to continue the text
+    pub fn fetch_api_sentence(&mut self) {
+        self.request_api_suggestion(true);
+    }
+
+    fn request_api_suggestion(&mut self, sentence: bool) {
         // If using Local provider, just update local suggestions
         if self.config.ai_provider == AiProvider::Local {
             self.update_suggestions();
@@ -588,6 +1103,13 @@ impl App {
             return;
         }
 
+        // Offline lock and spending cap fall back to the local Markov engine
+        if let Some(reason) = self.api_block_reason() {
+            self.update_suggestions();
+            self.status_message = Some(format!("{} - using local suggestions", reason));
+            return;
+        }
+
         if !self.config.has_api_key() {
             let provider = self.config.ai_provider;
             self.status_message = Some(format!("No {} API key - set env var", provider));
@@ -598,36 +1120,236 @@ impl App {
             return;
         }
 
-        let context = self.buffer.text_before_cursor();
+        let context = self.api_context();
         if context.len() < 10 {
             self.status_message = Some("Need more context for API suggestion".to_string());
             return;
         }
 
+        // The style goes into the key so a cached answer never outlives the voice it matched
+        let style = self.style_prompt();
+        let kind = if sentence { "sentence" } else { "word" };
+        let template = match &style {
+            Some(style) => format!("{} {}", kind, style),
+            None => kind.to_string(),
+        };
+        let key = CacheKey::new(
+            self.config.ai_provider,
+            self.config.current_model(),
+            &template,
+            &context,
+        );
+        if let Some(suggestion) = self.cache.get(&key) {
+            self.status_message = Some(if sentence {
+                self.sentence_suggestion = Some(suggestion);
+                "AI sentence ready (cached, Ctrl+Space to accept)".to_string()
+            } else {
+                self.api_suggestion = Some(suggestion);
+                "AI suggestion ready (cached, Tab to accept)".to_string()
+            });
+            self.track_suggestions();
+            return;
+        }
+        self.pending_cache_key = Some(key);
+
+        // Mask personal details before the context leaves the machine
+        let (ctx, redactions) = self.redactor.redact(&context);
+
         self.api_loading = true;
         let model = self.config.current_model_display();
-        self.status_message = Some(format!("Asking {}...", model));
+        self.status_message = Some(if redactions.is_empty() {
+            format!("Asking {}...", model)
+        } else {
+            format!("Asking {}... ({} redacted)", model, redactions.len())
+        });
 
         let config = self.config.clone();
+        let audit = AuditLog::new(&config, self.file_path.as_deref());
         let tx = self.api_tx.clone();
-        let ctx = context.clone();
 
         tokio::spawn(async move {
-            let result = match config.ai_provider {
-                AiProvider::Local => None, // Handled above
+            let provider = config.ai_provider;
+            let (model, result) = match provider {
+                AiProvider::Local => (String::new(), None), // Handled above
                 AiProvider::OpenAI => {
-                    let client = OpenAIClient::new(config);
-                    client.suggest(&ctx).await
+                    let model = config.openai_model.clone();
+                    let client = OpenAIClient::new(config).with_audit(audit).with_style(style);
+                    let result = if sentence {
+                        client.suggest_sentence(&ctx).await
+                    } else {
+                        client.suggest(&ctx).await
+                    };
+                    (model, result)
                 }
                 AiProvider::Anthropic => {
-                    let client = AnthropicClient::new(config);
-                    client.suggest(&ctx).await
+                    let client = AnthropicClient::new(config).with_audit(audit).with_style(style);
+                    let result = if sentence {
+                        client.suggest_sentence(&ctx).await
+                    } else {
+                        client.suggest(&ctx).await
+                    };
+                    (client.model_name().to_string(), result)
+                }
+            };
+
+            let suggestion = match result {
+                Some((mut suggestion, usage)) => {
+                    let _ = tx.send(ApiResponse::Usage { provider, model, usage }).await;
+                    suggestion.text = redactions.restore(&suggestion.text);
+                    Some(suggestion)
                 }
+                None => None,
+            };
+            let response = if sentence {
+                ApiResponse::SentenceSuggestion(suggestion)
+            } else {
+                ApiResponse::WordSuggestion(suggestion)
             };
-            let _ = tx.send(ApiResponse::WordSuggestion(result)).await;
+            let _ = tx.send(response).await;
         });
     }
 
+    /// Context for API suggestions. Once all earlier chapters have current
+    /// summaries, those stand in for their full text and only the current
+    /// chapter is sent verbatim; otherwise the full text is sent.
+    fn api_context(&self) -> String {
+        let before = self.buffer.text_before_cursor();
+        let (_, cursor_y) = self.buffer.cursor();
+        let chapters = split_chapters(self.buffer.lines());
+
+        let Some(memory) = self.summaries.memory_before(&chapters, cursor_y) else {
+            return before;
+        };
+        let chapter_start = chapters
+            .iter()
+            .rev()
+            .find(|c| c.start_line <= cursor_y)
+            .map(|c| c.start_line)
+            .unwrap_or(0);
+        let offset: usize = self.buffer.lines()[..chapter_start]
+            .iter()
+            .map(|l| l.len() + 1)
+            .sum();
+        format!("Earlier chapters, summarized:\n{}\n\n{}", memory, &before[offset..])
+    }
+
+    /// Prompt instructions describing the author's style, when the document
+    /// is long enough to have one
+    fn style_prompt(&self) -> Option<String> {
+        if !self.config.style_matching {
+            return None;
+        }
+        StyleProfile::analyze(&self.buffer.to_string()).map(|profile| profile.describe())
+    }
+
+    /// Summarize every chapter whose text changed since its last summary
+    pub fn summarize_chapters(&mut self) {
+        if self.summaries.pending > 0 {
+            return;
+        }
+
+        if self.config.ai_provider == AiProvider::Local {
+            self.status_message = Some("Summaries need an API provider - Ctrl+P to switch".to_string());
+            return;
+        }
+
+        if let Some(reason) = self.api_block_reason() {
+            self.status_message = Some(format!("{} - summaries unavailable", reason));
+            return;
+        }
+
+        if !self.config.has_api_key() {
+            let provider = self.config.ai_provider;
+            self.status_message = Some(format!("No {} API key - set env var", provider));
+            return;
+        }
+
+        let chapters = split_chapters(self.buffer.lines());
+        let stale: Vec<(String, String, String)> = self.summaries
+            .stale(&chapters)
+            .into_iter()
+            .map(|c| (c.title.clone(), c.hash(), c.text.clone()))
+            .collect();
+        if stale.is_empty() {
+            self.summaries.retain(&chapters);
+            self.summaries.save();
+            self.status_message = Some(format!("All {} chapter summaries are up to date", chapters.len()));
+            return;
+        }
+
+        self.summaries.pending = stale.len();
+        self.summaries.failed = 0;
+        self.status_message = Some(format!("Summarizing {} of {} chapters...", stale.len(), chapters.len()));
+
+        let requests: Vec<_> = stale
+            .into_iter()
+            .map(|(title, hash, text)| {
+                let (redacted, redactions) = self.redactor.redact(&text);
+                (title, hash, redacted, redactions)
+            })
+            .collect();
+        let config = self.config.clone();
+        let audit = AuditLog::new(&config, self.file_path.as_deref());
+        let tx = self.api_tx.clone();
+        let mut spent = self.usage.month().cost;
+
+        tokio::spawn(async move {
+            let provider = config.ai_provider;
+            let openai = OpenAIClient::new(config.clone()).with_audit(audit.clone());
+            let anthropic = AnthropicClient::new(config.clone()).with_audit(audit);
+
+            for (title, hash, text, redactions) in requests {
+                // The budget can run out partway through a long document
+                if config.monthly_budget.is_some_and(|budget| spent >= budget) {
+                    let _ = tx.send(ApiResponse::ChapterSummary { title, hash, summary: None }).await;
+                    continue;
+                }
+
+                let (model, result) = match provider {
+                    AiProvider::Local => (String::new(), None), // Handled above
+                    AiProvider::OpenAI => (config.openai_model.clone(), openai.summarize(&text).await),
+                    AiProvider::Anthropic => (anthropic.model_name().to_string(), anthropic.summarize(&text).await),
+                };
+
+                let summary = match result {
+                    Some(completion) => {
+                        let usage = completion.usage;
+                        spent += config.estimate_cost(&model, usage);
+                        let _ = tx.send(ApiResponse::Usage { provider, model, usage }).await;
+                        Some(redactions.restore(&completion.text))
+                    }
+                    None => None,
+                };
+                let _ = tx.send(ApiResponse::ChapterSummary { title, hash, summary }).await;
+            }
+        });
+    }
+
+    /// Why API calls are currently disallowed, if they are
+    pub fn api_block_reason(&self) -> Option<String> {
+        if self.config.offline_lock {
+            return Some("Offline lock".to_string());
+        }
+        if let Some(budget) = self.config.monthly_budget {
+            if self.usage.month().cost >= budget {
+                return Some(format!("Monthly budget of ${:.2} reached", budget));
+            }
+        }
+        None
+    }
+
+    pub fn toggle_offline_lock(&mut self) {
+        self.config.toggle_offline_lock();
+        if let Some(error) = &self.config.load_error {
+            self.status_message = Some(format!("Offline lock stays ON: {}", error));
+            return;
+        }
+        self.status_message = Some(format!(
+            "Offline lock: {}",
+            if self.config.offline_lock { "ON - no network requests" } else { "OFF" }
+        ));
+    }
+
     pub fn cycle_ai_provider(&mut self) {
         self.config.cycle_provider();
         let model = self.config.current_model_display();
@@ -645,6 +1367,54 @@ impl App {
         self.status_message = Some(format!("Model: {}", model));
     }
 
+    /// Switch the local engine to the next named model
+    pub fn cycle_markov_model(&mut self) {
+        let models = available_models();
+        if models.len() == 1 {
+            self.status_message = Some(format!(
+                "Only the {} model is installed - add more with `systymn build-model <name> <corpus>`",
+                models[0]
+            ));
+            return;
+        }
+        let next = models
+            .iter()
+            .position(|m| m == self.ai.model())
+            .map(|i| (i + 1) % models.len())
+            .unwrap_or(0);
+        let name = &models[next];
+        if self.ai.switch_model(name) {
+            self.learn_document();
+            self.status_message = Some(format!("Markov model: {} (order {})", name, self.ai.model_order()));
+            self.update_suggestions();
+        } else {
+            self.status_message = Some(format!("Could not load Markov model: {}", name));
+        }
+    }
+
+    pub fn markov_model(&self) -> &str {
+        self.ai.model()
+    }
+
+    pub fn sampling(&self) -> Sampling {
+        Sampling {
+            temperature: self.config.markov_temperature,
+            weirdness: self.config.markov_weirdness,
+        }
+    }
+
+    pub fn cycle_markov_temperature(&mut self) {
+        self.config.cycle_markov_temperature();
+        self.status_message = Some(format!("Markov temperature: {:.1}", self.config.markov_temperature));
+        self.update_suggestions();
+    }
+
+    pub fn cycle_markov_weirdness(&mut self) {
+        self.config.cycle_markov_weirdness();
+        self.status_message = Some(format!("Markov weirdness: {:.1}", self.config.markov_weirdness));
+        self.update_suggestions();
+    }
+
     pub fn cycle_ai_mode(&mut self) {
         self.config.cycle_mode();
         self.status_message = Some(format!("AI Mode: {}", self.config.ai_mode));
@@ -682,21 +1452,78 @@ impl App {
     }
 
     pub fn handle_api_response(&mut self, response: ApiResponse) {
-        self.api_loading = false;
         match response {
-            ApiResponse::WordSuggestion(Some(suggestion)) => {
-                self.api_suggestion = Some(suggestion);
-                self.status_message = Some("AI suggestion ready (Tab to accept)".to_string());
+            ApiResponse::WordSuggestion(suggestion) => {
+                self.api_loading = false;
+                let key = self.pending_cache_key.take();
+                self.status_message = Some(match suggestion {
+                    Some(suggestion) => {
+                        if let Some(key) = key {
+                            self.cache.insert(key, &suggestion);
+                        }
+                        self.api_suggestion = Some(suggestion);
+                        self.track_suggestions();
+                        "AI suggestion ready (Tab to accept)".to_string()
+                    }
+                    None => "No AI suggestion available".to_string(),
+                });
             }
-            ApiResponse::WordSuggestion(None) => {
-                self.status_message = Some("No AI suggestion available".to_string());
+            ApiResponse::SentenceSuggestion(suggestion) => {
+                self.api_loading = false;
+                let key = self.pending_cache_key.take();
+                self.status_message = Some(match suggestion {
+                    Some(suggestion) => {
+                        if let Some(key) = key {
+                            self.cache.insert(key, &suggestion);
+                        }
+                        self.sentence_suggestion = Some(suggestion);
+                        self.track_suggestions();
+                        "AI sentence ready (Ctrl+Space to accept)".to_string()
+                    }
+                    None => "No AI sentence available".to_string(),
+                });
             }
-            ApiResponse::SentenceSuggestion(Some(suggestion)) => {
-                self.sentence_suggestion = Some(suggestion);
-                self.status_message = Some("AI sentence ready (Ctrl+Space to accept)".to_string());
+            ApiResponse::ChatReply(reply) => {
+                self.chat.loading = false;
+                match reply {
+                    Some(text) => self.chat.push(ChatRole::Assistant, text),
+                    None => self.status_message = Some("No chat reply available".to_string()),
+                }
             }
-            ApiResponse::SentenceSuggestion(None) => {
-                self.status_message = Some("No AI sentence available".to_string());
+            ApiResponse::Critique(annotations) => {
+                self.critique.loading = false;
+                self.status_message = Some(match annotations {
+                    Some(annotations) if annotations.is_empty() => "Analysis found nothing to flag".to_string(),
+                    Some(annotations) => {
+                        let count = annotations.len();
+                        self.critique.annotations = annotations;
+                        format!("{} critique notes - move the cursor onto one to read it, Ctrl+R to clear", count)
+                    }
+                    None => "Analysis failed - no usable reply".to_string(),
+                });
+            }
+            ApiResponse::ChapterSummary { title, hash, summary } => {
+                self.summaries.pending = self.summaries.pending.saturating_sub(1);
+                match summary {
+                    Some(summary) => self.summaries.insert(ChapterSummary { title, hash, summary }),
+                    None => self.summaries.failed += 1,
+                }
+
+                self.status_message = Some(if self.summaries.pending > 0 {
+                    format!("Summarizing... {} chapters left", self.summaries.pending)
+                } else {
+                    self.summaries.retain(&split_chapters(self.buffer.lines()));
+                    self.summaries.save();
+                    match (self.summaries.failed, self.api_block_reason()) {
+                        (0, _) => "Chapter summaries updated".to_string(),
+                        (failed, Some(reason)) => format!("{} - {} chapters left unsummarized", reason, failed),
+                        (failed, None) => format!("Chapter summaries updated ({} failed)", failed),
+                    }
+                });
+            }
+            ApiResponse::Usage { provider, model, usage } => {
+                let cost = self.config.estimate_cost(&model, usage);
+                self.usage.record(provider, &model, usage, cost);
             }
         }
     }
@@ -707,6 +1534,10 @@ impl App {
         if let Ok(content) = fs::read_to_string("untitled.txt") {
             self.buffer = TextBuffer::from_text(&content);
             self.file_path = Some(PathBuf::from("untitled.txt"));
+            self.usage.set_document(self.file_path.as_deref());
+            self.summaries = SummaryStore::load(self.file_path.as_deref());
+            self.cast = Cast::load(self.file_path.as_deref());
+            self.learn_document();
             self.undo_stack.clear();
             self.redo_stack.clear();
             self.status_message = Some("File loaded: untitled.txt".to_string());
@@ -717,7 +1548,13 @@ impl App {
         let path = self.file_path.clone().unwrap_or_else(|| PathBuf::from("untitled.txt"));
         let content = self.buffer.to_string();
         if fs::write(&path, content).is_ok() {
+            if self.file_path.is_none() {
+                self.usage.set_document(Some(&path));
+                self.summaries.attach(&path);
+                self.cast.attach(&path);
+            }
             self.file_path = Some(path.clone());
+            self.learn_document();
             self.status_message = Some(format!("Saved: {}", path.display()));
         } else {
             self.status_message = Some("Failed to save file".to_string());
@@ -737,6 +1574,7 @@ impl App {
     }
 
     pub fn set_visible_height(&mut self, height: usize) {
+        self.visible_height = height;
         let (_, cursor_y) = self.buffer.cursor();
         if cursor_y >= self.scroll_offset + height.saturating_sub(2) {
             self.scroll_offset = cursor_y.saturating_sub(height.saturating_sub(3));
@@ -747,3 +1585,367 @@ impl App {
         &self.emoji
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::MockServer;
+    use crate::config::testing::TestConfigDir;
+    use std::time::Duration;
+
+    const REPLY: &str = "the lanterns hummed softly";
+
+    /// Local suggestions only, with nothing random unless a test asks
+    fn local_config() -> Config {
+        Config {
+            openai_api_key: None,
+            anthropic_api_key: None,
+            ai_provider: AiProvider::Local,
+            markov_weirdness: 0.0,
+            ..Config::default()
+        }
+    }
+
+    fn api_config(server: &MockServer, provider: AiProvider) -> Config {
+        let mut config = Config {
+            openai_api_key: Some("test-openai-key".to_string()),
+            anthropic_api_key: Some("test-anthropic-key".to_string()),
+            ai_provider: provider,
+            cache_enabled: false,
+            ..local_config()
+        };
+        server.configure(&mut config);
+        config
+    }
+
+    fn test_app(config: Config) -> (App, mpsc::Receiver<ApiResponse>) {
+        let (tx, rx) = mpsc::channel(10);
+        let mut app = App::new(config, tx);
+        app.buffer.insert_str("The clock tower struck thirteen and nobody");
+        (app, rx)
+    }
+
+    /// Feed API responses back into the app until `busy` says it settled
+    async fn settle(app: &mut App, rx: &mut mpsc::Receiver<ApiResponse>, busy: fn(&App) -> bool) {
+        while busy(app) {
+            let response = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .expect("API response timed out")
+                .expect("API channel closed");
+            app.handle_api_response(response);
+        }
+    }
+
+    /// Type a command on an empty line and press Enter
+    fn run(app: &mut App, typed: &str) -> String {
+        app.buffer = TextBuffer::new();
+        app.buffer.insert_str(typed);
+        app.handle_enter();
+        app.buffer.to_string()
+    }
+
+    #[tokio::test]
+    async fn api_suggestions_arrive_end_to_end() {
+        let _dir = TestConfigDir::new();
+        let server = MockServer::start().await.unwrap();
+        for (provider, path) in [(AiProvider::OpenAI, "/v1/chat/completions"), (AiProvider::Anthropic, "/v1/messages")] {
+            let (mut app, mut rx) = test_app(api_config(&server, provider));
+
+            app.fetch_api_suggestion();
+            settle(&mut app, &mut rx, |app| app.api_loading).await;
+
+            assert_eq!(app.api_suggestion.as_ref().expect("suggestion").text, format!(" {}", REPLY));
+            assert_eq!(app.usage.session().requests, 1);
+            assert!(app.usage.session().tokens > 0);
+            let (endpoint, body) = server.requests().pop().unwrap();
+            assert_eq!(endpoint, path);
+            assert_eq!(body["model"], app.config.current_model());
+
+            // Whole sentences are paid for like words
+            app.fetch_api_sentence();
+            settle(&mut app, &mut rx, |app| app.api_loading).await;
+            assert!(app.sentence_suggestion.expect("sentence").text.contains(REPLY));
+            assert_eq!(app.usage.session().requests, 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn chat_keeps_history_and_chapter_context() {
+        let _dir = TestConfigDir::new();
+        let server = MockServer::start().await.unwrap();
+        let (mut app, mut rx) = test_app(api_config(&server, AiProvider::OpenAI));
+        app.buffer = TextBuffer::from_text("# One\nThe ferry left at dawn.\n# Two\nThe clock tower struck thirteen.");
+        app.buffer.move_to_end();
+
+        for question in ["Is the opening weak?", "Why?"] {
+            app.chat.input = question.to_string();
+            app.send_chat_message();
+            settle(&mut app, &mut rx, |app| app.chat.loading).await;
+        }
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let messages = requests[1].1["messages"].as_array().unwrap();
+        let roles: Vec<&str> = messages.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+        let system = messages[0]["content"].as_str().unwrap();
+        assert!(system.contains("clock tower") && !system.contains("ferry"), "{}", system);
+        assert_eq!(messages[2]["content"], REPLY);
+
+        let before = app.buffer.to_string();
+        app.insert_chat_reply();
+        assert_eq!(app.buffer.to_string(), format!("{}{}", before, REPLY));
+        assert_eq!(app.usage.session().requests, 2);
+    }
+
+    #[tokio::test]
+    async fn chapter_summaries_refresh_only_changed_chapters() {
+        let _dir = TestConfigDir::new();
+        let server = MockServer::start().await.unwrap();
+        let (mut app, mut rx) = test_app(api_config(&server, AiProvider::OpenAI));
+        app.buffer = TextBuffer::from_text(
+            "# One\nThe ferry left at dawn.\n# Two\nThe clock tower struck thirteen.\n# Three\nNobody came.",
+        );
+
+        app.summarize_chapters();
+        settle(&mut app, &mut rx, |app| app.summaries.pending > 0).await;
+        assert_eq!(server.requests().len(), 3);
+
+        app.buffer.move_to_end();
+        app.buffer.insert_str(" Not even the cat.");
+        app.summarize_chapters();
+        settle(&mut app, &mut rx, |app| app.summaries.pending > 0).await;
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert!(requests[3].1["messages"][1]["content"].as_str().unwrap().contains("Not even the cat."));
+
+        // Earlier chapters reach suggestion prompts as summaries only
+        app.fetch_api_suggestion();
+        settle(&mut app, &mut rx, |app| app.api_loading).await;
+        let prompt = server.requests()[4].1["messages"][0]["content"].as_str().unwrap().to_string();
+        assert!(prompt.contains(&format!("- One: {}", REPLY)), "{}", prompt);
+        assert!(prompt.contains(&format!("- Two: {}", REPLY)), "{}", prompt);
+        assert!(!prompt.contains("ferry") && prompt.contains("Not even the cat."), "{}", prompt);
+
+        // Until an edited earlier chapter is summarized again, its full text is sent
+        app.buffer.set_cursor(0, 1);
+        app.buffer.insert_str("Fog. ");
+        app.buffer.move_to_end();
+        app.fetch_api_suggestion();
+        settle(&mut app, &mut rx, |app| app.api_loading).await;
+        let prompt = server.requests()[5].1["messages"][0]["content"].as_str().unwrap().to_string();
+        assert!(!prompt.contains("summarized") && prompt.contains("Fog. The ferry"), "{}", prompt);
+    }
+
+    #[tokio::test]
+    async fn the_budget_stops_summaries_partway() {
+        let _dir = TestConfigDir::new();
+        let server = MockServer::start().await.unwrap();
+        let (mut app, mut rx) = test_app(Config {
+            monthly_budget: Some(1e-9),
+            ..api_config(&server, AiProvider::OpenAI)
+        });
+        app.buffer = TextBuffer::from_text("# One\nThe ferry left.\n# Two\nThe clock struck.\n# Three\nNobody came.");
+        assert_eq!(app.api_block_reason(), None);
+
+        app.summarize_chapters();
+        settle(&mut app, &mut rx, |app| app.summaries.pending > 0).await;
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(app.summaries.failed, 2);
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Monthly budget of $0.00 reached - 2 chapters left unsummarized")
+        );
+
+        // Blocked calls fall back to local suggestions without a request
+        app.fetch_api_suggestion();
+        assert!(!app.api_loading);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn offline_lock_blocks_api_calls_until_lifted() {
+        let _dir = TestConfigDir::new();
+        let (mut app, _rx) = test_app(Config {
+            openai_api_key: Some("test-openai-key".to_string()),
+            ai_provider: AiProvider::OpenAI,
+            offline_lock: true,
+            ..local_config()
+        });
+        assert_eq!(app.api_block_reason().as_deref(), Some("Offline lock"));
+        app.summarize_chapters();
+        assert_eq!(app.summaries.pending, 0);
+        app.toggle_offline_lock();
+        assert_eq!(app.api_block_reason(), None);
+
+        app.config.monthly_budget = Some(0.0);
+        assert_eq!(app.api_block_reason().as_deref(), Some("Monthly budget of $0.00 reached"));
+    }
+
+    #[test]
+    fn markov_models_cycle_once_more_are_built() {
+        let _dir = TestConfigDir::new();
+        let (mut app, _rx) = test_app(local_config());
+        app.cycle_markov_model();
+        assert_eq!(app.markov_model(), "seed");
+        assert!(app.status_message.as_ref().unwrap().contains("build-model"));
+
+        let mut noir = crate::ai::MarkovEngine::new();
+        noir.learn_document("Rain fell on the city. The city never slept.");
+        noir.save(&crate::ai::model_path("noir").unwrap()).unwrap();
+        app.cycle_markov_model();
+        assert_eq!(app.markov_model(), "noir");
+        app.cycle_markov_model();
+        assert_eq!(app.markov_model(), "seed");
+    }
+
+    #[test]
+    fn seeded_runs_repeat_exactly() {
+        let _dir = TestConfigDir::new();
+        let run = |seed: u64| {
+            let (mut app, _rx) = test_app(Config { seed: Some(seed), ..local_config() });
+            app.emoji_mode = true;
+            let mut seen = Vec::new();
+            for word in "struck the hour. In the library /n met /c with /o /emoji".split(' ') {
+                for c in std::iter::once(' ').chain(word.chars()) {
+                    app.insert_char(c);
+                    for _ in 0..10 {
+                        app.tick();
+                    }
+                    seen.push(app.current_suggestion.as_ref().map(|s| s.text.clone()));
+                    seen.push(app.sentence_suggestion.as_ref().map(|s| s.text.clone()));
+                }
+                if word.starts_with('/') {
+                    app.handle_enter();
+                }
+            }
+            (seen, app.buffer.to_string())
+        };
+
+        let (suggestions, text) = run(7);
+        assert_eq!(run(7), (suggestions.clone(), text.clone()));
+        assert!(!text.contains('/'), "{}", text);
+        assert!(suggestions.iter().flatten().count() > 10);
+    }
+
+    #[test]
+    fn command_mistakes_stay_in_the_text() {
+        let _dir = TestConfigDir::new();
+        let (mut app, _rx) = test_app(local_config());
+        for (typed, complaint) in [
+            ("/c --country=zz", "zz"),
+            ("/n:q", ":q"),
+            ("/o x0", "x0"),
+            ("/zz", "/zz"),
+            ("/tpl nope", "nope"),
+            ("/tpl", "which template"),
+        ] {
+            assert_eq!(run(&mut app, typed), typed);
+            assert!(app.command_preview.as_ref().is_some_and(|p| p.contains(complaint)), "{:?}", app.command_preview);
+        }
+        // A slash inside a word is no command
+        assert_eq!(run(&mut app, "and/or"), "and/or\n");
+    }
+
+    #[test]
+    fn enter_runs_only_the_command_at_the_cursor() {
+        let _dir = TestConfigDir::new();
+        let (mut app, _rx) = test_app(local_config());
+        for typed in ["He typed /quit and left.", "The /n met us there", "/o x0 of them"] {
+            assert_eq!(run(&mut app, typed), format!("{}\n", typed));
+            assert!(app.command_preview.is_none(), "{:?}", app.command_preview);
+        }
+        assert!(!run(&mut app, "The /n").contains('/'));
+    }
+
+    #[test]
+    fn templates_share_repeated_references() {
+        let dir = TestConfigDir::new();
+        let templates = dir.path().join("templates");
+        fs::create_dir_all(&templates).unwrap();
+        fs::write(templates.join("rivals.txt"), "# Two rivals\n{name:f#a} and {n:m} met.\n{#a} left before {n:m} did.").unwrap();
+        fs::write(templates.join("mirror.txt"), "{tpl mirror}").unwrap();
+        let (mut app, _rx) = test_app(local_config());
+
+        let text = run(&mut app, "/tpl rivals");
+        let (first, second) = text.split_once('\n').unwrap();
+        let (a, rest) = first.split_once(" and ").unwrap();
+        let b = rest.strip_suffix(" met.").unwrap();
+        assert_eq!(second, format!("{} left before {} did.", a, b));
+
+        // Built-ins, with nested generators and spelled-out variants
+        for name in ["/tpl:prompt", "/tpl quest", "/tpl scene"] {
+            let text = run(&mut app, name);
+            assert!(!text.contains(['{', '}', '/']) && text.ends_with('.'), "{}", text);
+        }
+
+        assert_eq!(run(&mut app, "/tpl mirror"), "/tpl mirror");
+        assert!(app.command_preview.as_ref().unwrap().contains("too deeply"));
+    }
+
+    #[test]
+    fn generated_names_join_the_cast_and_come_back() {
+        let dir = TestConfigDir::new();
+        let document = dir.path().join("novel.md");
+        let (mut app, _rx) = test_app(local_config());
+        app.cast = Cast::load(Some(&document));
+
+        let detective = run(&mut app, "/n:f --role=detective");
+        let city = run(&mut app, "/c");
+
+        // Two letters are enough to find her again
+        app.buffer = TextBuffer::new();
+        let prefix: String = detective.chars().take(2).collect::<String>().to_lowercase();
+        app.buffer.insert_str(&format!("/cast {}", &prefix[..1]));
+        app.insert_char(prefix.chars().nth(1).unwrap());
+        assert!(app.command_preview.as_ref().unwrap().contains(&format!("{} (detective)", detective)));
+        app.accept_suggestion();
+        app.handle_enter();
+        assert_eq!(app.buffer.to_string(), detective);
+
+        let members = Cast::load(Some(&document)).members().to_vec();
+        assert_eq!(members.len(), 2);
+        assert_eq!((members[0].name.as_str(), members[0].kind), (detective.as_str(), CastKind::Character));
+        assert_eq!((members[1].name.as_str(), members[1].kind), (city.as_str(), CastKind::Place));
+    }
+
+    #[test]
+    fn menu_runs_the_chosen_command_or_waits_for_its_argument() {
+        let _dir = TestConfigDir::new();
+        let (mut app, _rx) = test_app(local_config());
+        let female = include_str!("../data/names_female.txt");
+
+        app.buffer = TextBuffer::new();
+        app.insert_char('/');
+        app.insert_char('n');
+        app.command_menu_down();
+        // The periodic refresh keeps the choice
+        for _ in 0..30 {
+            app.tick();
+        }
+        assert_eq!(app.command_menu.as_ref().unwrap().selected().name, "nf");
+        app.handle_enter();
+        let name = app.buffer.to_string();
+        assert!(female.lines().any(|line| line == name), "{}", name);
+        assert!(app.command_menu.is_none());
+
+        app.buffer = TextBuffer::new();
+        for c in "/tp".chars() {
+            app.insert_char(c);
+        }
+        app.handle_enter();
+        assert_eq!(app.buffer.to_string(), "/tpl ");
+        assert!(app.command_menu.is_none());
+
+        // A dismissed menu stays closed until the name changes
+        app.buffer = TextBuffer::new();
+        app.insert_char('/');
+        app.dismiss_or_exit();
+        for _ in 0..30 {
+            app.tick();
+        }
+        assert!(app.command_menu.is_none());
+        app.insert_char('c');
+        assert_eq!(app.command_menu.as_ref().unwrap().selected().name, "c");
+    }
+}
@@ -1,5 +1,5 @@
 mod text;
 mod history;
 
-pub use text::TextBuffer;
+pub use text::{heading_title, TextBuffer};
 pub use history::{EditOperation, History};
@@ -9,6 +9,16 @@ pub struct TextBuffer {
     selection_anchor: Option<(usize, usize)>,
 }
 
+/// The title of a Markdown heading line: one or more `#`, a space, then
+/// text. `#hashtag` and a bare `#` are not headings.
+pub fn heading_title(line: &str) -> Option<&str> {
+    let rest = line.trim_start().trim_start_matches('#');
+    if rest.len() == line.trim_start().len() || !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(rest.trim()).filter(|title| !title.is_empty())
+}
+
 impl Default for TextBuffer {
     fn default() -> Self {
         Self::new()
@@ -360,6 +370,22 @@ impl TextBuffer {
         result
     }
 
+    /// The Markdown chapter around the cursor: from the nearest heading at
+    /// or above it up to the next heading
+    pub fn chapter_at_cursor(&self) -> String {
+        let is_heading = |line: &String| heading_title(line).is_some();
+        let start = self.lines[..=self.cursor_y]
+            .iter()
+            .rposition(is_heading)
+            .unwrap_or(0);
+        let end = self.lines[self.cursor_y + 1..]
+            .iter()
+            .position(is_heading)
+            .map(|i| self.cursor_y + 1 + i)
+            .unwrap_or(self.lines.len());
+        self.lines[start..end].join("\n")
+    }
+
     pub fn last_word(&self) -> Option<String> {
         let text = self.text_before_cursor();
         text.split_whitespace().last().map(String::from)
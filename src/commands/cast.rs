@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CastKind {
+    Character,
+    Place,
+    Object,
+}
+
+impl CastKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            CastKind::Character => "character",
+            CastKind::Place => "place",
+            CastKind::Object => "object",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CastMember {
+    pub name: String,
+    pub kind: CastKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+/// Every name, city and object generated for one document, in the order
+/// they first appeared, kept in a sidecar file beside it
+/// (`novel.md` → `novel.md.cast.json`)
+#[derive(Debug, Default)]
+pub struct Cast {
+    path: Option<PathBuf>,
+    members: Vec<CastMember>,
+}
+
+impl Cast {
+    pub fn load(document: Option<&Path>) -> Self {
+        let path = document.map(sidecar_path);
+        let members = path
+            .as_ref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path, members }
+    }
+
+    /// Give an unsaved document's cast a home once it is saved
+    pub fn attach(&mut self, document: &Path) {
+        self.path = Some(sidecar_path(document));
+        self.save();
+    }
+
+    pub fn members(&self) -> &[CastMember] {
+        &self.members
+    }
+
+    /// Add a newly generated member, or give one already in the cast a
+    /// new role
+    pub fn record(&mut self, name: &str, kind: CastKind, role: Option<&str>) {
+        match self.members.iter_mut().find(|m| m.name.eq_ignore_ascii_case(name)) {
+            Some(member) => {
+                if role.is_some() {
+                    member.role = role.map(String::from);
+                }
+            }
+            None => self.members.push(CastMember {
+                name: name.to_string(),
+                kind,
+                role: role.map(String::from),
+            }),
+        }
+        self.save();
+    }
+
+    /// Members whose name starts with `query`, ignoring case
+    pub fn matches(&self, query: &str) -> Vec<&CastMember> {
+        let query = query.to_lowercase();
+        self.members
+            .iter()
+            .filter(|m| m.name.to_lowercase().starts_with(&query))
+            .collect()
+    }
+
+    /// The one member `query` names: exactly, or as the only match
+    pub fn find(&self, query: &str) -> Result<&CastMember, String> {
+        if self.members.is_empty() {
+            return Err("the cast is empty; /n, /c or /o adds to it".to_string());
+        }
+        if let Some(member) = self.members.iter().find(|m| m.name.eq_ignore_ascii_case(query)) {
+            return Ok(member);
+        }
+        match self.matches(query).as_slice() {
+            [] => Err(format!("nobody in the cast is called '{}'", query)),
+            [member] => Ok(member),
+            several => {
+                let names: Vec<&str> = several.iter().map(|m| m.name.as_str()).collect();
+                Err(format!("which one? {}", names.join(", ")))
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let Some(path) = &self.path else { return };
+        if let Ok(content) = serde_json::to_string_pretty(&self.members) {
+            let _ = fs::write(path, content);
+        }
+    }
+}
+
+fn sidecar_path(document: &Path) -> PathBuf {
+    let mut name = document.file_name().unwrap_or_default().to_os_string();
+    name.push(".cast.json");
+    document.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+
+    #[test]
+    fn members_are_found_recast_and_kept() {
+        let dir = TestConfigDir::new();
+        let document = dir.path().join("novel.md");
+        let mut cast = Cast::load(Some(&document));
+        cast.record("Ada Quill", CastKind::Character, None);
+        cast.record("Adamant Bay", CastKind::Place, None);
+        cast.record("ada quill", CastKind::Character, Some("detective"));
+
+        assert_eq!(cast.members().len(), 2);
+        assert_eq!(cast.find("ADA QUILL").unwrap().role.as_deref(), Some("detective"));
+        assert_eq!(cast.find("adam").unwrap().name, "Adamant Bay");
+        assert!(cast.find("ada").unwrap_err().contains("Ada Quill, Adamant Bay"));
+        assert!(cast.find("Nobody").is_err());
+
+        assert!(dir.path().join("novel.md.cast.json").exists());
+        let reloaded = Cast::load(Some(&document));
+        let kept: Vec<_> = reloaded.members().iter().map(|m| (m.name.as_str(), m.kind)).collect();
+        assert_eq!(kept, [("Ada Quill", CastKind::Character), ("Adamant Bay", CastKind::Place)]);
+    }
+}
@@ -1,9 +1,11 @@
 use rand::prelude::*;
-use super::parser::{Command, NameVariant, LocationVariant, EmotionVariant, TimeVariant};
+use super::invent::invent;
+use super::parser::{Command, NameVariant, LocationVariant, EmotionVariant, TimeVariant, NameStyle};
 
 const NAMES_MALE: &str = include_str!("../../data/names_male.txt");
 const NAMES_FEMALE: &str = include_str!("../../data/names_female.txt");
 const NAMES_NEUTRAL: &str = include_str!("../../data/names_neutral.txt");
+/// One city per line, then a tab and its ISO country code
 const CITIES: &str = include_str!("../../data/cities.txt");
 const LOCATIONS_INTERIOR: &str = include_str!("../../data/locations_interior.txt");
 const LOCATIONS_OUTDOOR: &str = include_str!("../../data/locations_outdoor.txt");
@@ -17,9 +19,7 @@ const ACTIONS: &str = include_str!("../../data/actions.txt");
 pub struct Generators;
 
 impl Generators {
-    pub fn generate(command: &Command) -> String {
-        let mut rng = thread_rng();
-
+    pub fn generate(command: &Command, rng: &mut impl Rng) -> String {
         match command {
             Command::Name(variant) => {
                 let pool = match variant {
@@ -34,10 +34,18 @@ impl Generators {
                         }
                     }
                 };
-                Self::random_line(pool, &mut rng)
+                Self::random_line(pool, rng)
             }
 
-            Command::City => Self::random_line(CITIES, &mut rng),
+            Command::City(country) => {
+                let cities: Vec<&str> = CITIES
+                    .lines()
+                    .filter_map(|line| line.split_once('\t'))
+                    .filter(|(_, code)| country.as_deref().is_none_or(|country| country == *code))
+                    .map(|(city, _)| city)
+                    .collect();
+                cities.choose(rng).map(|s| s.to_string()).unwrap_or_else(|| "unknown".to_string())
+            }
 
             Command::Location(variant) => {
                 let pool = match variant {
@@ -47,7 +55,7 @@ impl Generators {
                         if rng.gen_bool(0.5) { LOCATIONS_INTERIOR } else { LOCATIONS_OUTDOOR }
                     }
                 };
-                Self::random_line(pool, &mut rng)
+                Self::random_line(pool, rng)
             }
 
             Command::Emotion(variant) => {
@@ -58,10 +66,10 @@ impl Generators {
                         if rng.gen_bool(0.5) { EMOTIONS_POSITIVE } else { EMOTIONS_NEGATIVE }
                     }
                 };
-                Self::random_line(pool, &mut rng)
+                Self::random_line(pool, rng)
             }
 
-            Command::Object => Self::random_line(OBJECTS, &mut rng),
+            Command::Object => Self::random_line(OBJECTS, rng),
 
             Command::Time(variant) => {
                 let pool = match variant {
@@ -71,19 +79,27 @@ impl Generators {
                         if rng.gen_bool(0.5) { TIMES_DAY } else { TIMES_NIGHT }
                     }
                 };
-                Self::random_line(pool, &mut rng)
+                Self::random_line(pool, rng)
             }
 
-            Command::Action => Self::random_line(ACTIONS, &mut rng),
+            Command::Action => Self::random_line(ACTIONS, rng),
 
-            // Emoji is handled separately in app.rs via EmojiEngine
-            Command::Emoji(_) => "\u{2728}".to_string(),
+            Command::Invent(invention) => invent(invention, rng),
 
-            Command::Unknown(_) => "???".to_string(),
+            Command::List(list) => list.items.choose(rng).cloned().unwrap_or_else(|| "unknown".to_string()),
+
+            // Emoji, templates and the cast are handled separately in app.rs
+            Command::Emoji(_) => "\u{2728}".to_string(),
+            Command::Template(_) | Command::Cast(_) => String::new(),
         }
     }
 
-    fn random_line(data: &str, rng: &mut ThreadRng) -> String {
+    /// Whether any city is in the country with this ISO code
+    pub fn has_country(code: &str) -> bool {
+        CITIES.lines().any(|line| line.split_once('\t').is_some_and(|(_, c)| c == code))
+    }
+
+    fn random_line(data: &str, rng: &mut impl Rng) -> String {
         let lines: Vec<&str> = data.lines().filter(|l| !l.is_empty()).collect();
         lines.choose(rng).map(|s| s.to_string()).unwrap_or_else(|| "unknown".to_string())
     }
@@ -94,7 +110,8 @@ impl Generators {
             Command::Name(NameVariant::Female) => "[female name]".to_string(),
             Command::Name(NameVariant::Neutral) => "[neutral name]".to_string(),
             Command::Name(NameVariant::Any) => "[name]".to_string(),
-            Command::City => "[city]".to_string(),
+            Command::City(Some(country)) => format!("[city in {}]", country),
+            Command::City(None) => "[city]".to_string(),
             Command::Location(LocationVariant::Interior) => "[interior]".to_string(),
             Command::Location(LocationVariant::Outdoor) => "[outdoor]".to_string(),
             Command::Location(LocationVariant::Any) => "[location]".to_string(),
@@ -106,9 +123,21 @@ impl Generators {
             Command::Time(TimeVariant::Night) => "[nighttime]".to_string(),
             Command::Time(TimeVariant::Any) => "[time]".to_string(),
             Command::Action => "[action]".to_string(),
+            Command::Invent(invention) => {
+                let style = match invention.style {
+                    NameStyle::Elvish => "elvish",
+                    NameStyle::Nordic => "nordic",
+                    NameStyle::Latinate => "latinate",
+                };
+                format!("[invented {} name]", style)
+            }
+            Command::List(list) => format!("[{}]", list.description.as_deref().unwrap_or(&list.name)),
             Command::Emoji(Some(cat)) => format!("[emoji: {}]", cat),
             Command::Emoji(None) => "[emoji]".to_string(),
-            Command::Unknown(s) => format!("[unknown: {}]", s),
+            Command::Template(Some(name)) => format!("[template: {}]", name),
+            Command::Template(None) => "[template]".to_string(),
+            Command::Cast(Some(query)) => format!("[cast: {}]", query),
+            Command::Cast(None) => "[cast]".to_string(),
         }
     }
 }
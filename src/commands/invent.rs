@@ -0,0 +1,152 @@
+use rand::distributions::WeightedIndex;
+use rand::prelude::*;
+use std::collections::HashMap;
+use super::parser::{Invention, NameStyle};
+
+const ELVISH: &str = include_str!("../../data/invent_elvish.txt");
+const NORDIC: &str = include_str!("../../data/invent_nordic.txt");
+const LATINATE: &str = include_str!("../../data/invent_latinate.txt");
+
+/// Letters of context each next letter is picked by. Two keeps the sound of
+/// a style without copying whole names from a list this short.
+const ORDER: usize = 2;
+/// Pads names on both ends, so starts and ends are learned like letters
+const EDGE: char = '^';
+const DEFAULT_LENGTH: (usize, usize) = (4, 10);
+/// Names spelled before settling for the closest fit
+const ATTEMPTS: usize = 300;
+
+/// Which letters follow each run of `ORDER` letters, with counts, in the
+/// order they were first seen so a seeded rng picks the same way each time
+type Chains = HashMap<String, Vec<(char, u32)>>;
+
+/// Spell a new name in the style's sound, as near the asked-for shape as
+/// the style allows. Names already in the style's list are never returned.
+pub fn invent(invention: &Invention, rng: &mut impl Rng) -> String {
+    let known: Vec<String> = names(invention.style).map(str::to_lowercase).collect();
+    let chains = train(&known);
+    let (min, max) = invention
+        .length
+        .unwrap_or_else(|| match invention.syllables {
+            // Room for the syllables asked for, in any style
+            Some(n) => (n + 1, (n * 4).max(DEFAULT_LENGTH.1)),
+            None => DEFAULT_LENGTH,
+        });
+
+    let mut best: Option<(usize, String)> = None;
+    for _ in 0..ATTEMPTS {
+        let Some(name) = spell(&chains, max, rng) else { continue };
+        if known.contains(&name) {
+            continue;
+        }
+        let len = name.chars().count();
+        let mut miss = min.saturating_sub(len);
+        if let Some(n) = invention.syllables {
+            miss += syllables(&name).abs_diff(n);
+        }
+        if miss == 0 {
+            return capitalize(&name);
+        }
+        if best.as_ref().is_none_or(|(fewest, _)| miss < *fewest) {
+            best = Some((miss, name));
+        }
+    }
+    best.map(|(_, name)| capitalize(&name)).unwrap_or_else(|| "unknown".to_string())
+}
+
+fn names(style: NameStyle) -> impl Iterator<Item = &'static str> {
+    let list = match style {
+        NameStyle::Elvish => ELVISH,
+        NameStyle::Nordic => NORDIC,
+        NameStyle::Latinate => LATINATE,
+    };
+    list.lines().map(str::trim).filter(|l| !l.is_empty())
+}
+
+fn train(names: &[String]) -> Chains {
+    let mut chains = Chains::new();
+    for name in names {
+        let padded: Vec<char> = std::iter::repeat_n(EDGE, ORDER)
+            .chain(name.chars())
+            .chain(std::iter::once(EDGE))
+            .collect();
+        for window in padded.windows(ORDER + 1) {
+            let key: String = window[..ORDER].iter().collect();
+            let nexts = chains.entry(key).or_default();
+            match nexts.iter_mut().find(|(c, _)| *c == window[ORDER]) {
+                Some((_, count)) => *count += 1,
+                None => nexts.push((window[ORDER], 1)),
+            }
+        }
+    }
+    chains
+}
+
+/// Walk the chains from the start of a name to its end, giving up once the
+/// name grows past `max` letters
+fn spell(chains: &Chains, max: usize, rng: &mut impl Rng) -> Option<String> {
+    let mut name = String::new();
+    let mut key: String = std::iter::repeat_n(EDGE, ORDER).collect();
+    loop {
+        let nexts = chains.get(&key)?;
+        let pick = WeightedIndex::new(nexts.iter().map(|(_, count)| *count)).ok()?;
+        let next = nexts[pick.sample(rng)].0;
+        if next == EDGE {
+            return Some(name);
+        }
+        name.push(next);
+        if name.chars().count() > max {
+            return None;
+        }
+        key.remove(0);
+        key.push(next);
+    }
+}
+
+/// Runs of vowels, with "y" a vowel anywhere but first
+fn syllables(name: &str) -> usize {
+    let mut count = 0;
+    let mut in_vowel = false;
+    for (i, c) in name.chars().enumerate() {
+        let vowel = "aeiou".contains(c) || (c == 'y' && i > 0);
+        if vowel && !in_vowel {
+            count += 1;
+        }
+        in_vowel = vowel;
+    }
+    count
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn invented_names_fit_the_shape_asked_for() {
+        let invention = Invention { style: NameStyle::Elvish, syllables: Some(3), length: Some((6, 9)) };
+        for seed in 0..20 {
+            let name = invent(&invention, &mut StdRng::seed_from_u64(seed));
+            assert!(name.starts_with(char::is_uppercase), "{}", name);
+            assert!((6..=9).contains(&name.chars().count()), "{}", name);
+            assert!(!ELVISH.lines().any(|known| known == name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn seeds_repeat_and_syllables_are_vowel_runs() {
+        let invention = Invention::new(NameStyle::Nordic);
+        let spell = |seed| invent(&invention, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(spell(7), spell(7));
+        assert_eq!(syllables("eldarion"), 3);
+        assert_eq!(syllables("yrsa"), 1);
+    }
+}
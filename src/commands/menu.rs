@@ -0,0 +1,111 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use crate::ai::stable_hash;
+use super::generators::Generators;
+use super::parser::{Command, CommandParser};
+use super::registry::CommandRegistry;
+
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    pub name: String,
+    pub description: String,
+    /// Something the command could make, or what it makes
+    pub sample: String,
+    pub command: Command,
+}
+
+/// The dropdown of commands matching the name being typed after a slash
+#[derive(Debug, Clone)]
+pub struct CommandMenu {
+    pub items: Vec<MenuItem>,
+    pub selected: usize,
+    /// Byte offset of the slash in the text before the cursor
+    pub start: usize,
+}
+
+impl CommandMenu {
+    /// A menu for the command name at the end of `context`, if it could
+    /// be the start of any
+    pub fn open(registry: &CommandRegistry, context: &str) -> Option<Self> {
+        let (start, typed) = CommandParser::typed_name(context)?;
+        let items: Vec<MenuItem> = registry
+            .complete(typed)
+            .into_iter()
+            .map(|(name, description, command)| MenuItem {
+                name: name.to_string(),
+                description: description.to_string(),
+                sample: sample(name, command),
+                command: command.clone(),
+            })
+            .collect();
+        if items.is_empty() {
+            return None;
+        }
+        Some(Self { items, selected: 0, start })
+    }
+
+    pub fn up(&mut self) {
+        self.selected = self.selected.checked_sub(1).unwrap_or(self.items.len() - 1);
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % self.items.len();
+    }
+
+    pub fn selected(&self) -> &MenuItem {
+        &self.items[self.selected]
+    }
+}
+
+/// A real value for generators, drawn from an rng seeded by the command's
+/// name so it holds still while the menu is open; the placeholder for the
+/// commands the app expands itself
+fn sample(name: &str, command: &Command) -> String {
+    match command {
+        Command::Emoji(_) | Command::Template(_) | Command::Cast(_) => Generators::preview(command),
+        _ => Generators::generate(command, &mut StdRng::seed_from_u64(stable_hash(name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(menu: &CommandMenu) -> Vec<&str> {
+        menu.items.iter().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn menu_ranks_describes_and_samples() {
+        let registry = CommandRegistry::new();
+
+        // "/e" is an emotion first, though emoji is on offer too
+        let menu = CommandMenu::open(&registry, "She felt /e").unwrap();
+        assert_eq!((menu.start, names(&menu)[0]), (9, "e"));
+        assert!(names(&menu).contains(&"emoji"));
+        let menu = CommandMenu::open(&registry, "/em").unwrap();
+        assert_eq!(names(&menu)[0], "emoji");
+        assert!(menu.items[0].description.contains(":face"));
+
+        // Each entry carries a real sample, the same every time
+        let menu = CommandMenu::open(&registry, "/n").unwrap();
+        assert_eq!(names(&menu)[..4], ["n", "nf", "nm", "nx"]);
+        let female = include_str!("../../data/names_female.txt");
+        assert!(female.lines().any(|name| name == menu.items[1].sample));
+        assert_eq!(CommandMenu::open(&registry, "/n").unwrap().items[1].sample, menu.items[1].sample);
+
+        assert!(CommandMenu::open(&registry, "/n:f").is_none());
+        assert!(CommandMenu::open(&registry, "/qq").is_none());
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut menu = CommandMenu::open(&CommandRegistry::new(), "/n").unwrap();
+        let last = menu.items.len() - 1;
+        menu.up();
+        assert_eq!(menu.selected, last);
+        menu.down();
+        menu.down();
+        assert_eq!(menu.selected().name, "nf");
+    }
+}
@@ -1,5 +1,14 @@
 mod parser;
 mod generators;
+mod invent;
+mod cast;
+mod menu;
+mod registry;
+mod templates;
 
-pub use parser::{Command, CommandParser};
+pub use parser::{Command, CommandParser, Invocation};
+pub use cast::{Cast, CastKind};
 pub use generators::Generators;
+pub use menu::CommandMenu;
+pub use registry::CommandRegistry;
+pub use templates::{pieces, Expansion, Piece};
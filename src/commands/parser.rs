@@ -1,14 +1,22 @@
+use std::rc::Rc;
+use super::cast::CastKind;
+use super::generators::Generators;
+use super::registry::{CommandRegistry, UserList};
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     Name(NameVariant),
-    City,
+    City(Option<String>), // Optional country code
     Location(LocationVariant),
     Emotion(EmotionVariant),
     Object,
     Time(TimeVariant),
     Action,
+    Invent(Invention),
+    List(Rc<UserList>), // From the user's generators folder
+    Template(Option<String>), // Expanded by the app
+    Cast(Option<String>), // Someone already generated, by name
     Emoji(Option<String>), // Optional category
-    Unknown(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -40,88 +48,331 @@ pub enum TimeVariant {
     Night,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NameStyle {
+    Elvish,
+    Nordic,
+    Latinate,
+}
+
+/// An invented name: its style, and the shape asked for with `--syl=N` and
+/// `--len=N` or `--len=MIN-MAX`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invention {
+    pub style: NameStyle,
+    pub syllables: Option<usize>,
+    pub length: Option<(usize, usize)>,
+}
+
+impl Invention {
+    pub fn new(style: NameStyle) -> Self {
+        Self { style, syllables: None, length: None }
+    }
+}
+
+impl Command {
+    /// What this command adds to the document's cast, if anything
+    pub fn cast_kind(&self) -> Option<CastKind> {
+        match self {
+            Command::Name(_) | Command::Invent(_) => Some(CastKind::Character),
+            Command::City(_) => Some(CastKind::Place),
+            Command::Object => Some(CastKind::Object),
+            _ => None,
+        }
+    }
+
+    /// Apply a `--key=value` option
+    fn set_option(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match (self, key) {
+            (Command::City(country), "country") => {
+                let code = value.to_lowercase();
+                if !Generators::has_country(&code) {
+                    return Err(format!("no cities for country '{}'", value));
+                }
+                *country = Some(code);
+            }
+            (Command::Invent(invention), "syl") => match value.parse() {
+                Ok(n) if n > 0 => invention.syllables = Some(n),
+                _ => return Err(format!("--syl wants a number of syllables, not '{}'", value)),
+            },
+            (Command::Invent(invention), "len") => {
+                let (min, max) = value.split_once('-').unwrap_or((value, value));
+                match (min.parse(), max.parse()) {
+                    (Ok(min), Ok(max)) if 0 < min && min <= max => invention.length = Some((min, max)),
+                    _ => return Err(format!("--len wants N or MIN-MAX, not '{}'", value)),
+                }
+            }
+            _ => return Err(format!("no option --{}", key)),
+        }
+        Ok(())
+    }
+}
+
+/// A command with its arguments: `/n:f 3`, `/c --country=jp`, `/o x2 and`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    pub command: Command,
+    /// How many to generate
+    pub count: usize,
+    /// Word before the last of several ("and", "or"); commas otherwise
+    pub joiner: Option<String>,
+    /// What a generated character, place or object is to the story
+    pub role: Option<String>,
+}
+
+impl Invocation {
+    /// Join generated values into a list that reads as prose
+    pub fn join(&self, values: &[String]) -> String {
+        match (values.split_last(), &self.joiner) {
+            (Some((last, rest)), Some(joiner)) if !rest.is_empty() => {
+                format!("{} {} {}", rest.join(", "), joiner, last)
+            }
+            _ => values.join(", "),
+        }
+    }
+}
+
+const MAX_COUNT: usize = 20;
+/// Variants spelled out, as templates tend to (`{location:interior}`)
+const VARIANT_WORDS: &[(&str, &str)] = &[
+    ("male", "m"),
+    ("female", "f"),
+    ("neutral", "x"),
+    ("interior", "i"),
+    ("outdoor", "o"),
+    ("positive", "+"),
+    ("negative", "-"),
+    ("day", "d"),
+    ("night", "n"),
+];
+const JOINERS: &[&str] = &["and", "or"];
+
 pub struct CommandParser;
 
 impl CommandParser {
-    pub fn parse(input: &str) -> Option<(Command, usize, usize)> {
-        let trimmed = input.trim_end();
+    /// The last slash command before the cursor, or why it doesn't parse,
+    /// with the byte range it covers (up to the mistake). `None` if there
+    /// is no command.
+    ///
+    /// Grammar: `/name[:variant]` then any of `N` or `xN` (a count), `and`
+    /// or `or` after a count (a joiner), and `--key=value` (an option).
+    /// The first word that is none of these ends the command and is left
+    /// in the text, so `/n met` stays "… met".
+    pub fn parse(registry: &CommandRegistry, input: &str) -> Option<(Result<Invocation, String>, usize, usize)> {
+        let (start, text) = Self::slash(input)?;
+        let text = text.trim_end();
+        if !text.starts_with(|c: char| !c.is_whitespace()) {
+            return None;
+        }
+        Some(match Self::invocation(registry, text) {
+            Ok((invocation, len)) => (Ok(invocation), start, start + 1 + len),
+            Err((message, len)) => (Err(message), start, start + 1 + len),
+        })
+    }
+
+    /// The command name being typed at the end of `input`, and where its
+    /// slash is: "/em" but not "/em " or "/n:f"
+    pub fn typed_name(input: &str) -> Option<(usize, &str)> {
+        let (start, text) = Self::slash(input)?;
+        if text.contains(|c: char| c.is_whitespace() || c == ':') {
+            return None;
+        }
+        Some((start, text))
+    }
 
-        // Find the last slash command in the text
-        if let Some(slash_pos) = trimmed.rfind('/') {
-            let after_slash = &trimmed[slash_pos + 1..];
+    /// The last slash on the cursor's line that starts a word, so "and/or"
+    /// is no command, and the text after it
+    fn slash(input: &str) -> Option<(usize, &str)> {
+        let line_start = input.rfind('\n').map_or(0, |i| i + 1);
+        let line = &input[line_start..];
+        let start = line.rfind('/')?;
+        if line[..start].chars().last().is_some_and(|c| !c.is_whitespace() && !"([{\"'".contains(c)) {
+            return None;
+        }
+        Some((line_start + start, &line[start + 1..]))
+    }
+
+    /// A whole reference from a template, such as "n:f 3" in `{n:f 3}`
+    pub fn parse_reference(registry: &CommandRegistry, text: &str) -> Result<Invocation, String> {
+        let (invocation, len) = Self::invocation(registry, text).map_err(|(message, _)| message)?;
+        match text[len..].trim() {
+            "" => Ok(invocation),
+            rest => Err(format!("unexpected '{}' in {{{}}}", rest, text)),
+        }
+    }
 
-            // Check if there's a space after the command (command is complete)
-            let cmd_end = after_slash.find(' ').unwrap_or(after_slash.len());
-            let cmd_str = &after_slash[..cmd_end];
+    /// The command at the start of `text`, and how much of it it covers;
+    /// a mistake covers the text up to the word that is wrong
+    fn invocation(registry: &CommandRegistry, text: &str) -> Result<(Invocation, usize), (String, usize)> {
+        let mut words = Self::tokenize(text);
+        let Some((0, name)) = words.next() else {
+            return Err(("type a command after /".to_string(), 0));
+        };
+        let command = Self::parse_command(registry, name).map_err(|message| (message, name.len()))?;
+        let mut invocation = Invocation { command, count: 1, joiner: None, role: None };
+        let mut end = name.len();
+        let mut counted = false;
+        let error = |message: String, end: usize| Err((format!("/{}: {}", name, message), end));
 
-            let command = Self::parse_command(cmd_str)?;
-            let start = slash_pos;
-            let end = slash_pos + 1 + cmd_end;
+        for (offset, word) in words {
+            if let Some(option) = word.strip_prefix("--") {
+                let Some((key, value)) = option.split_once('=') else {
+                    return error(format!("--{} needs a value, as in --{}=…", option, option), offset + word.len());
+                };
+                if key == "role" && (invocation.command.cast_kind().is_some() || matches!(invocation.command, Command::Cast(_))) {
+                    invocation.role = Some(value.to_string());
+                } else if let Err(message) = invocation.command.set_option(key, value) {
+                    return error(message, offset + word.len());
+                }
+            } else if let Command::Cast(query) = &mut invocation.command {
+                // Names may have spaces and numbers in them
+                let query = query.get_or_insert_with(String::new);
+                if !query.is_empty() {
+                    query.push(' ');
+                }
+                query.push_str(word);
+            } else if let Some(count) = Self::parse_count(word) {
+                match count {
+                    _ if counted => return error(format!("count given twice ('{}')", word), offset + word.len()),
+                    Some(n @ 1..=MAX_COUNT) => invocation.count = n,
+                    _ => return error(format!("count must be 1 to {}, not '{}'", MAX_COUNT, word), offset + word.len()),
+                }
+                counted = true;
+            } else if counted && invocation.joiner.is_none() && JOINERS.contains(&word) {
+                invocation.joiner = Some(word.to_string());
+            } else if let Command::Template(template @ None) = &mut invocation.command {
+                *template = Some(word.to_lowercase());
+            } else {
+                break;
+            }
+            end = offset + word.len();
+        }
+
+        if let Command::Template(template) = &invocation.command {
+            let names = || registry.template_names().collect::<Vec<_>>().join(", ");
+            match template {
+                None => return error(format!("which template? {}", names()), end),
+                Some(template) if registry.template(template).is_none() => {
+                    return error(format!("no template '{}' (there are {})", template, names()), end);
+                }
+                Some(_) => {}
+            }
+        }
+        Ok((invocation, end))
+    }
+
+    /// Words after the slash with their offsets
+    fn tokenize(text: &str) -> impl Iterator<Item = (usize, &str)> {
+        text.split(' ')
+            .scan(0, |offset, word| {
+                let at = *offset;
+                *offset += word.len() + 1;
+                Some((at, word))
+            })
+            .filter(|(_, word)| !word.is_empty())
+    }
 
-            Some((command, start, end))
+    /// `Some(None)` for a word that starts like a count but isn't one, like "3rd"
+    fn parse_count(word: &str) -> Option<Option<usize>> {
+        let digits = word.strip_prefix('x').unwrap_or(word);
+        if word.starts_with(|c: char| c.is_ascii_digit()) || (word.len() > 1 && digits.chars().all(|c| c.is_ascii_digit())) {
+            Some(digits.parse().ok())
         } else {
             None
         }
     }
 
-    fn parse_command(s: &str) -> Option<Command> {
+    /// `name` or `name:variant`, where the variant is a built-in suffix
+    /// (`/n:f` is `/nf`) or an emoji category (`/emoji:face`)
+    fn parse_command(registry: &CommandRegistry, s: &str) -> Result<Command, String> {
         let s = s.to_lowercase();
-        match s.as_str() {
-            // Names
-            "n" => Some(Command::Name(NameVariant::Any)),
-            "nm" => Some(Command::Name(NameVariant::Male)),
-            "nf" => Some(Command::Name(NameVariant::Female)),
-            "nx" => Some(Command::Name(NameVariant::Neutral)),
-
-            // City
-            "c" => Some(Command::City),
-
-            // Location
-            "l" => Some(Command::Location(LocationVariant::Any)),
-            "li" => Some(Command::Location(LocationVariant::Interior)),
-            "lo" => Some(Command::Location(LocationVariant::Outdoor)),
-
-            // Emotion
-            "e" => Some(Command::Emotion(EmotionVariant::Any)),
-            "e+" => Some(Command::Emotion(EmotionVariant::Positive)),
-            "e-" => Some(Command::Emotion(EmotionVariant::Negative)),
-
-            // Object
-            "o" => Some(Command::Object),
-
-            // Time
-            "t" => Some(Command::Time(TimeVariant::Any)),
-            "td" => Some(Command::Time(TimeVariant::Day)),
-            "tn" => Some(Command::Time(TimeVariant::Night)),
-
-            // Action
-            "a" => Some(Command::Action),
-
-            // Emoji
-            "emoji" => Some(Command::Emoji(None)),
-
-            _ => {
-                // Handle emoji with category (e.g., "emoji face", "emoji nature")
-                if s.starts_with("emoji ") {
-                    let category = s.strip_prefix("emoji ").map(String::from);
-                    Some(Command::Emoji(category))
-                } else if !s.is_empty() {
-                    Some(Command::Unknown(s))
-                } else {
-                    None
-                }
+        let (typed, variant) = s.split_once(':').unwrap_or((&s, ""));
+        if typed.is_empty() {
+            return Err("type a command after /".to_string());
+        }
+        let name = registry.canonical(typed);
+        match (registry.get(name), variant) {
+            (None, _) => Err(format!("unknown command /{}", typed)),
+            (Some(command), "") => Ok(command.clone()),
+            (Some(Command::Emoji(None)), category) => Ok(Command::Emoji(Some(category.to_string()))),
+            (Some(Command::Template(None)), template) => Ok(Command::Template(Some(template.to_string()))),
+            (Some(_), variant) => {
+                let suffix = VARIANT_WORDS
+                    .iter()
+                    .find(|(word, _)| *word == variant)
+                    .map_or(variant, |(_, suffix)| suffix);
+                registry
+                    .get(&format!("{}{}", name, suffix))
+                    .cloned()
+                    .ok_or_else(|| format!("/{} has no variant :{}", typed, variant))
             }
         }
     }
+}
 
-    pub fn is_partial_command(input: &str) -> Option<&str> {
-        let trimmed = input.trim_end();
-        if let Some(slash_pos) = trimmed.rfind('/') {
-            let after = &trimmed[slash_pos + 1..];
-            if !after.contains(' ') && after.len() <= 3 {
-                return Some(after);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(typed: &str) -> Result<(Invocation, usize, usize), String> {
+        let (invocation, start, end) = CommandParser::parse(&CommandRegistry::new(), typed).expect("a command");
+        invocation.map(|invocation| (invocation, start, end))
+    }
+
+    #[test]
+    fn commands_take_counts_joiners_and_options() {
+        let (names, _, _) = parse("/n:f 3").unwrap();
+        assert_eq!((names.command, names.count), (Command::Name(NameVariant::Female), 3));
+
+        let (objects, _, _) = parse("/o x3 and").unwrap();
+        assert_eq!((objects.count, objects.joiner.as_deref()), (3, Some("and")));
+        let values = ["a key", "a lamp", "a map"].map(String::from);
+        assert_eq!(objects.join(&values), "a key, a lamp and a map");
+
+        let (city, _, _) = parse("/c --country=JP").unwrap();
+        assert_eq!(city.command, Command::City(Some("jp".to_string())));
+
+        // Only options right after the command are taken
+        let typed = "So /norse --len=5 and his brother";
+        let (invented, start, end) = parse(typed).unwrap();
+        assert_eq!(&typed[start..end], "/norse --len=5");
+        assert!(matches!(invented.command, Command::Invent(Invention { length: Some((5, 5)), .. })));
+    }
+
+    #[test]
+    fn mistakes_are_explained() {
+        for (typed, complaint) in [
+            ("/c --country=zz", "zz"),
+            ("/n:q", ":q"),
+            ("/o x0", "x0"),
+            ("/n 2 3", "twice"),
+            ("/elf --syl=many", "many"),
+            ("/zz", "/zz"),
+        ] {
+            let message = parse(typed).unwrap_err();
+            assert!(message.contains(complaint), "{}: {}", typed, message);
+        }
+
+        // A mistake covers the text up to the word that is wrong
+        for (typed, mistake) in [("He typed /quit and left.", "/quit"), ("/o x0 of them", "/o x0")] {
+            let (parsed, start, end) = CommandParser::parse(&CommandRegistry::new(), typed).unwrap();
+            assert!(parsed.is_err());
+            assert_eq!(&typed[start..end], mistake);
         }
-        None
+    }
+
+    #[test]
+    fn only_slashes_that_start_a_word_are_commands() {
+        let registry = CommandRegistry::new();
+        assert!(CommandParser::parse(&registry, "and/or").is_none());
+        assert!(CommandParser::parse(&registry, "/ n").is_none());
+        assert!(CommandParser::parse(&registry, "(/n").is_some());
+        assert_eq!(CommandParser::typed_name("Say /em"), Some((4, "em")));
+        assert_eq!(CommandParser::typed_name("Say /em "), None);
+
+        // Templates spell out names and variants
+        let reference = CommandParser::parse_reference(&registry, "name:female 2").unwrap();
+        assert_eq!((reference.command, reference.count), (Command::Name(NameVariant::Female), 2));
+        assert!(CommandParser::parse_reference(&registry, "n extra").is_err());
     }
 }
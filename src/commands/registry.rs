@@ -0,0 +1,248 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::rc::Rc;
+use crate::config::config_dir;
+use super::templates::{load_templates, Template};
+use super::parser::{Command, NameVariant, LocationVariant, EmotionVariant, TimeVariant, Invention, NameStyle};
+
+/// A generator read from `generators/<name>.txt` in the config directory:
+/// one entry per line, with `#` lines as comments or directives
+#[derive(Debug, PartialEq)]
+pub struct UserList {
+    pub name: String,
+    pub description: Option<String>,
+    pub items: Vec<String>,
+}
+
+/// Every slash command by name: the built-ins, then the user's lists, which
+/// may take over a built-in's name
+pub struct CommandRegistry {
+    commands: HashMap<String, Entry>,
+    templates: BTreeMap<String, Template>,
+}
+
+struct Entry {
+    command: Command,
+    description: String,
+}
+
+/// Built-ins by their whole names, as templates use them (`{name:f}`)
+const LONG_NAMES: &[(&str, &str)] = &[
+    ("name", "n"),
+    ("city", "c"),
+    ("location", "l"),
+    ("emotion", "e"),
+    ("object", "o"),
+    ("time", "t"),
+    ("action", "a"),
+];
+
+impl CommandRegistry {
+    /// Only the built-in commands
+    pub fn new() -> Self {
+        let commands = [
+            ("n", "Name", Command::Name(NameVariant::Any)),
+            ("nm", "Male name", Command::Name(NameVariant::Male)),
+            ("nf", "Female name", Command::Name(NameVariant::Female)),
+            ("nx", "Neutral name", Command::Name(NameVariant::Neutral)),
+            ("c", "City (--country=jp)", Command::City(None)),
+            ("l", "Location", Command::Location(LocationVariant::Any)),
+            ("li", "Interior location", Command::Location(LocationVariant::Interior)),
+            ("lo", "Outdoor location", Command::Location(LocationVariant::Outdoor)),
+            ("e", "Emotion", Command::Emotion(EmotionVariant::Any)),
+            ("e+", "Positive emotion", Command::Emotion(EmotionVariant::Positive)),
+            ("e-", "Negative emotion", Command::Emotion(EmotionVariant::Negative)),
+            ("o", "Object", Command::Object),
+            ("t", "Time", Command::Time(TimeVariant::Any)),
+            ("td", "Time of day", Command::Time(TimeVariant::Day)),
+            ("tn", "Time of night", Command::Time(TimeVariant::Night)),
+            ("a", "Action verb", Command::Action),
+            ("elf", "Invented Elvish name (--syl=N --len=MIN-MAX)", Command::Invent(Invention::new(NameStyle::Elvish))),
+            ("norse", "Invented Nordic name (--syl=N --len=MIN-MAX)", Command::Invent(Invention::new(NameStyle::Nordic))),
+            ("latin", "Invented Latinate name (--syl=N --len=MIN-MAX)", Command::Invent(Invention::new(NameStyle::Latinate))),
+            ("emoji", "Emoji (:face, :nature, :animal, :object, :food, :heart)", Command::Emoji(None)),
+            ("tpl", "Expand a template", Command::Template(None)),
+            ("cast", "Someone already generated", Command::Cast(None)),
+        ];
+        Self {
+            commands: commands
+                .into_iter()
+                .map(|(name, description, command)| (name.to_string(), Entry { command, description: description.to_string() }))
+                .collect(),
+            templates: BTreeMap::new(),
+        }
+    }
+
+    /// The built-ins plus every list in the config directory's `generators`
+    /// folder. A list named `tavern.txt` becomes `/tavern`, and a line
+    /// `# aliases: inn, pub` adds `/inn` and `/pub`. Names win over aliases.
+    /// Templates for `/tpl` load alongside.
+    pub fn load() -> Self {
+        let mut registry = Self::new();
+        registry.templates = load_templates();
+        let mut paths: Vec<_> = config_dir()
+            .and_then(|dir| fs::read_dir(dir.join("generators")).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .collect();
+        paths.sort();
+
+        let mut lists = Vec::new();
+        for path in paths {
+            let (Some(stem), Ok(text)) = (path.file_stem(), fs::read_to_string(&path)) else { continue };
+            let (list, aliases) = parse_list(&stem.to_string_lossy().to_lowercase(), &text);
+            if !list.items.is_empty() {
+                lists.push((Rc::new(list), aliases));
+            }
+        }
+        for (list, aliases) in &lists {
+            for alias in aliases {
+                let description = format!("{} (/{})", list.describe(), list.name);
+                registry.commands.insert(alias.clone(), Entry { command: Command::List(Rc::clone(list)), description });
+            }
+        }
+        for (list, _) in lists {
+            let description = list.describe();
+            registry.commands.insert(list.name.clone(), Entry { command: Command::List(list), description });
+        }
+        registry
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name).map(|entry| &entry.command)
+    }
+
+    /// Commands that `typed` could be the start of, with their
+    /// descriptions, best first: an exact name, then a built-in's whole
+    /// name ("emotion" for `/e`), then longer names, shortest first. So
+    /// `/e` is an emotion, and `/em` an emoji.
+    pub fn complete(&self, typed: &str) -> Vec<(&str, &str, &Command)> {
+        let typed = typed.to_lowercase();
+        let mut matches: Vec<(u8, &str, &Entry)> = self
+            .commands
+            .iter()
+            .filter_map(|(name, entry)| {
+                let long = LONG_NAMES
+                    .iter()
+                    .find(|(_, short)| short == name)
+                    .map(|(long, _)| *long)
+                    .filter(|long| !self.commands.contains_key(*long));
+                let rank = if *name == typed {
+                    0
+                } else if long == Some(typed.as_str()) {
+                    1
+                } else if name.starts_with(&typed) {
+                    2
+                } else if long.is_some_and(|long| long.starts_with(&typed)) {
+                    3
+                } else {
+                    return None;
+                };
+                Some((rank, name.as_str(), entry))
+            })
+            .collect();
+        matches.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.len().cmp(&b.1.len())).then(a.1.cmp(b.1)));
+        matches
+            .into_iter()
+            .map(|(_, name, entry)| (name, entry.description.as_str(), &entry.command))
+            .collect()
+    }
+
+    /// The short name for a built-in's whole name, unless a list has it
+    pub fn canonical<'a>(&self, name: &'a str) -> &'a str {
+        match LONG_NAMES.iter().find(|(long, _)| *long == name) {
+            Some((_, short)) if !self.commands.contains_key(name) => short,
+            _ => name,
+        }
+    }
+
+    pub fn template(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    pub fn template_names(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(String::as_str)
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UserList {
+    fn describe(&self) -> String {
+        self.description.clone().unwrap_or_else(|| format!("From generators/{}.txt", self.name))
+    }
+}
+
+/// A list's entries, description and aliases from its file
+fn parse_list(name: &str, text: &str) -> (UserList, Vec<String>) {
+    let mut list = UserList { name: name.to_string(), description: None, items: Vec::new() };
+    let mut aliases = Vec::new();
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let Some(comment) = line.strip_prefix('#') else {
+            list.items.push(line.to_string());
+            continue;
+        };
+        match comment.split_once(':').map(|(key, value)| (key.trim(), value.trim())) {
+            Some(("aliases", value)) => aliases.extend(
+                value
+                    .split(',')
+                    .map(|alias| alias.trim().trim_start_matches('/').to_lowercase())
+                    .filter(|alias| !alias.is_empty() && !alias.contains(' ')),
+            ),
+            Some(("description", value)) => list.description = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    (list, aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+
+    fn items(registry: &CommandRegistry, name: &str) -> Vec<String> {
+        match registry.get(name) {
+            Some(Command::List(list)) => list.items.clone(),
+            other => panic!("/{} is {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn user_lists_add_and_override_commands() {
+        let dir = TestConfigDir::new();
+        let generators = dir.path().join("generators");
+        fs::create_dir_all(&generators).unwrap();
+        fs::write(generators.join("tavern.txt"), "# aliases: inn, /pub\n# description: Taverns\nThe Gilded Eel\n").unwrap();
+        fs::write(generators.join("a.txt"), "# Actions for this book only\nskulked\n").unwrap();
+        fs::write(generators.join("empty.txt"), "# Nothing yet\n").unwrap();
+
+        let registry = CommandRegistry::load();
+        for name in ["tavern", "inn", "pub"] {
+            assert_eq!(items(&registry, name), ["The Gilded Eel"]);
+        }
+        assert_eq!(items(&registry, "a"), ["skulked"]);
+        assert!(registry.get("empty").is_none());
+        assert!(CommandRegistry::new().get("tavern").is_none());
+
+        let described: Vec<(&str, &str)> = registry.complete("inn").into_iter().map(|(n, d, _)| (n, d)).collect();
+        assert_eq!(described, [("inn", "Taverns (/tavern)")]);
+    }
+
+    #[test]
+    fn names_rank_exact_then_whole_then_longer() {
+        let registry = CommandRegistry::new();
+        let names = |typed| registry.complete(typed).into_iter().map(|(name, _, _)| name).collect::<Vec<_>>();
+        assert_eq!(names("e")[..4], ["e", "e+", "e-", "elf"]);
+        assert_eq!(names("em"), ["emoji", "e"]);
+        assert_eq!(names("emotion"), ["e"]);
+        assert_eq!(registry.canonical("city"), "c");
+        assert_eq!(registry.canonical("tavern"), "tavern");
+    }
+}
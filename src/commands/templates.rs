@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use crate::config::config_dir;
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("prompt", include_str!("../../data/templates/prompt.txt")),
+    ("quest", include_str!("../../data/templates/quest.txt")),
+    ("scene", include_str!("../../data/templates/scene.txt")),
+];
+
+/// Templates nested deeper than this are taken to call themselves
+const MAX_DEPTH: usize = 8;
+
+/// Text with `{…}` references to generators. A reference is a slash
+/// command without its slash (`{n:f}`, `{o x2 and}`), optionally labelled
+/// (`{name#rival}`) so `{#rival}` can repeat it. Lines starting with `#`
+/// are comments; the first describes the template.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub description: Option<String>,
+    pub text: String,
+}
+
+impl Template {
+    fn parse(source: &str) -> Self {
+        let mut description = None;
+        let mut lines = Vec::new();
+        for line in source.lines() {
+            match line.strip_prefix('#') {
+                Some(comment) => {
+                    description = description.or_else(|| Some(comment.trim().to_string()));
+                }
+                None => lines.push(line),
+            }
+        }
+        Self { description, text: lines.join("\n").trim().to_string() }
+    }
+}
+
+/// The built-in templates, then `templates/*.txt` from the config
+/// directory, which may replace them
+pub fn load_templates() -> BTreeMap<String, Template> {
+    let mut templates: BTreeMap<String, Template> = BUILTIN
+        .iter()
+        .map(|(name, source)| (name.to_string(), Template::parse(source)))
+        .collect();
+    let paths = config_dir()
+        .and_then(|dir| fs::read_dir(dir.join("templates")).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"));
+    for path in paths {
+        if let (Some(stem), Ok(source)) = (path.file_stem(), fs::read_to_string(&path)) {
+            templates.insert(stem.to_string_lossy().to_lowercase(), Template::parse(&source));
+        }
+    }
+    templates
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Piece<'a> {
+    Text(&'a str),
+    /// The whole reference, and the command in it without its label
+    Reference(&'a str, &'a str),
+    /// A label alone, as in `{#rival}`
+    Recall(&'a str),
+}
+
+/// Split text into plain runs and references
+pub fn pieces(text: &str) -> Result<Vec<Piece<'_>>, String> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..].find('}').ok_or_else(|| format!("unclosed {{ in \"{}\"", rest))? + open;
+        pieces.push(Piece::Text(&rest[..open]));
+        let reference = rest[open + 1..close].trim();
+        pieces.push(match reference.split_once('#') {
+            Some(("", label)) => Piece::Recall(label),
+            Some((command, _)) => Piece::Reference(reference, command.trim()),
+            None => Piece::Reference(reference, reference),
+        });
+        rest = &rest[close + 1..];
+    }
+    pieces.push(Piece::Text(rest));
+    Ok(pieces)
+}
+
+/// What one expansion has generated so far, so each reference (and each
+/// label) means the same thing everywhere in it, however deeply nested
+#[derive(Default)]
+pub struct Expansion {
+    values: HashMap<String, String>,
+    depth: usize,
+}
+
+impl Expansion {
+    pub fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("templates nested too deeply; does one include itself?".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    pub fn get(&self, reference: &str) -> Option<&String> {
+        self.values.get(reference)
+    }
+
+    pub fn recall(&self, label: &str) -> Result<&String, String> {
+        self.values
+            .get(&format!("#{}", label))
+            .ok_or_else(|| format!("{{#{}}} comes before any {{…#{}}}", label, label))
+    }
+
+    pub fn remember(&mut self, reference: &str, value: &str) {
+        if let Some((_, label)) = reference.split_once('#') {
+            self.values.insert(format!("#{}", label), value.to_string());
+        }
+        self.values.insert(reference.to_string(), value.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::testing::TestConfigDir;
+
+    #[test]
+    fn references_labels_and_recalls_split_out() {
+        assert_eq!(
+            pieces("{name:f#a} met {n:m}. {#a} left.").unwrap(),
+            [
+                Piece::Text(""),
+                Piece::Reference("name:f#a", "name:f"),
+                Piece::Text(" met "),
+                Piece::Reference("n:m", "n:m"),
+                Piece::Text(". "),
+                Piece::Recall("a"),
+                Piece::Text(" left."),
+            ]
+        );
+        assert!(pieces("{n met").is_err());
+    }
+
+    #[test]
+    fn expansions_share_values_and_stop_recursion() {
+        let mut expansion = Expansion::default();
+        expansion.remember("name:f#a", "Ada");
+        assert_eq!(expansion.get("name:f#a").map(String::as_str), Some("Ada"));
+        assert_eq!(expansion.recall("a").map(String::as_str), Ok("Ada"));
+        assert!(expansion.recall("b").is_err());
+
+        let depth = (0..=MAX_DEPTH).position(|_| expansion.enter().is_err());
+        assert_eq!(depth, Some(MAX_DEPTH));
+    }
+
+    #[test]
+    fn user_templates_join_and_replace_the_built_ins() {
+        let dir = TestConfigDir::new();
+        fs::create_dir_all(dir.path().join("templates")).unwrap();
+        fs::write(dir.path().join("templates/Quest.txt"), "# My quest\n{n} seeks {o}.").unwrap();
+        fs::write(dir.path().join("templates/duel.txt"), "{n} and {n} draw.").unwrap();
+
+        let templates = load_templates();
+        let names: Vec<&str> = templates.keys().map(String::as_str).collect();
+        assert_eq!(names, ["duel", "prompt", "quest", "scene"]);
+        assert_eq!(
+            templates["quest"],
+            Template { description: Some("My quest".to_string()), text: "{n} seeks {o}.".to_string() }
+        );
+        assert_eq!(templates["duel"].description, None);
+    }
+}
@@ -1,6 +1,11 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+use crate::ai::TokenUsage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AiProvider {
     Local,
     OpenAI,
@@ -36,33 +41,179 @@ impl std::fmt::Display for AiMode {
     }
 }
 
-pub const OPENAI_MODELS: &[(&str, &str)] = &[
-    ("gpt-4o", "GPT-4o (Best)"),
-    ("gpt-4o-mini", "GPT-4o Mini (Fast)"),
-    ("gpt-4-turbo", "GPT-4 Turbo"),
-    ("gpt-3.5-turbo", "GPT-3.5 Turbo (Cheap)"),
-];
+/// A selectable model, as listed in the config file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelSpec {
+    pub id: String,
+    pub name: String,
+    /// Overrides the sampling temperature of every request to this model
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Caps the completion token limit of every request to this model; each
+    /// kind of request keeps its own smaller budget
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+}
+
+impl ModelSpec {
+    fn new(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            temperature: None,
+            max_tokens: None,
+        }
+    }
+}
+
+fn default_openai_models() -> Vec<ModelSpec> {
+    vec![
+        ModelSpec::new("gpt-4o", "GPT-4o (Best)"),
+        ModelSpec::new("gpt-4o-mini", "GPT-4o Mini (Fast)"),
+        ModelSpec::new("gpt-4-turbo", "GPT-4 Turbo"),
+        ModelSpec::new("gpt-3.5-turbo", "GPT-3.5 Turbo (Cheap)"),
+    ]
+}
 
-pub const ANTHROPIC_MODELS: &[(&str, &str)] = &[
-    ("claude-sonnet-4-20250514", "Claude Sonnet 4 (Best)"),
-    ("claude-3-5-sonnet-20241022", "Claude 3.5 Sonnet"),
-    ("claude-3-5-haiku-20241022", "Claude 3.5 Haiku (Fast)"),
-    ("claude-3-haiku-20240307", "Claude 3 Haiku (Cheap)"),
-];
+fn default_anthropic_models() -> Vec<ModelSpec> {
+    vec![
+        ModelSpec::new("claude-sonnet-4-20250514", "Claude Sonnet 4 (Best)"),
+        ModelSpec::new("claude-3-5-sonnet-20241022", "Claude 3.5 Sonnet"),
+        ModelSpec::new("claude-3-5-haiku-20241022", "Claude 3.5 Haiku (Fast)"),
+        ModelSpec::new("claude-3-haiku-20240307", "Claude 3 Haiku (Cheap)"),
+    ]
+}
 
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+fn merge_model_prices<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, ModelPrice>, D::Error> {
+    let mut prices = default_model_prices();
+    prices.extend(HashMap::<String, ModelPrice>::deserialize(deserializer)?);
+    Ok(prices)
+}
+
+fn default_model_prices() -> HashMap<String, ModelPrice> {
+    [
+        ("gpt-4o", 2.50, 10.00),
+        ("gpt-4o-mini", 0.15, 0.60),
+        ("gpt-4-turbo", 10.00, 30.00),
+        ("gpt-3.5-turbo", 0.50, 1.50),
+        ("claude-sonnet-4-20250514", 3.00, 15.00),
+        ("claude-3-5-sonnet-20241022", 3.00, 15.00),
+        ("claude-3-5-haiku-20241022", 0.80, 4.00),
+        ("claude-3-haiku-20240307", 0.25, 1.25),
+    ]
+    .into_iter()
+    .map(|(model, input, output)| (model.to_string(), ModelPrice { input, output }))
+    .collect()
+}
+
+/// What to mask in a context before it is sent to a cloud provider
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    pub emails: bool,
+    pub phone_numbers: bool,
+    pub urls: bool,
+    /// Names and other terms to mask, matched case-insensitively as whole words
+    pub terms: Vec<String>,
+    /// Extra regular expressions to mask
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            emails: true,
+            phone_numbers: true,
+            urls: true,
+            terms: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Whether the built-in mock server records real API traffic or replays it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureMode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FixtureConfig {
+    pub mode: FixtureMode,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub openai_api_key: Option<String>,
     pub openai_model: String,
-    pub openai_model_index: usize,
+    pub openai_models: Vec<ModelSpec>,
     pub anthropic_api_key: Option<String>,
     pub anthropic_model: String,
-    pub anthropic_model_index: usize,
+    pub anthropic_models: Vec<ModelSpec>,
     pub ai_provider: AiProvider,
     pub ai_mode: AiMode,
     pub pulse_speed_ms: u64,
     pub suggestion_delay_ms: u64,
     pub auto_suggest: bool,
+    pub cache_enabled: bool,
+    pub cache_capacity: usize,
+    pub cache_on_disk: bool,
+    /// Entries in the config file are merged over the built-in prices
+    #[serde(deserialize_with = "merge_model_prices")]
+    pub model_prices: HashMap<String, ModelPrice>,
+    /// Hard cap in USD on API spend per calendar month
+    pub monthly_budget: Option<f64>,
+    /// Never touch the network, whatever the provider and mode
+    pub offline_lock: bool,
+    pub redaction: RedactionConfig,
+    /// Record every outbound AI request in `audit.jsonl`
+    pub audit_log: bool,
+    pub openai_base_url: String,
+    pub anthropic_base_url: String,
+    /// Route API calls through the in-process mock server in record or replay mode
+    pub fixtures: Option<FixtureConfig>,
+    /// Describe the document's style (sentence length, vocabulary, dialogue,
+    /// tense, point of view) in suggestion prompts
+    pub style_matching: bool,
+    /// Model the local engine starts with: `seed` for the built-in text, or
+    /// the name of a file in `~/.config/systymn/models`
+    pub markov_model: String,
+    /// n-gram order of the seed model, 2 (bigrams) to 4. Model files keep
+    /// the order they were built with.
+    pub markov_order: usize,
+    /// Directory of the author's past work for the Markov engine to learn from
+    pub corpus_dir: Option<PathBuf>,
+    /// How many seed occurrences one occurrence in the author's own text,
+    /// from the corpus or the open document, is worth
+    pub author_weight: u32,
+    /// Word list, one word per line, to complete from beyond the built-in one
+    /// (`/usr/share/dict/words`, for instance)
+    pub dictionary: Option<PathBuf>,
+    /// Seed for every random choice, so the same keystrokes give the same
+    /// suggestions and generator output. Random when unset.
+    pub seed: Option<u64>,
+    /// Sampling temperature of local sentence suggestions
+    pub markov_temperature: f32,
+    /// 0 to 1: how often local suggestions take unlikely turns
+    pub markov_weirdness: f32,
+    /// Why `config.json` exists but couldn't be used. The defaults loaded in
+    /// its place are locked offline, since the budget, offline lock and
+    /// redaction rules it sets are unknown.
+    #[serde(skip)]
+    pub load_error: Option<String>,
 }
 
 impl Default for Config {
@@ -70,20 +221,86 @@ impl Default for Config {
         Self {
             openai_api_key: std::env::var("OPENAI_API_KEY").ok(),
             openai_model: "gpt-4o-mini".to_string(),
-            openai_model_index: 1,
+            openai_models: default_openai_models(),
             anthropic_api_key: std::env::var("ANTHROPIC_API_KEY").ok(),
             anthropic_model: "claude-3-5-haiku-20241022".to_string(),
-            anthropic_model_index: 2,
+            anthropic_models: default_anthropic_models(),
             ai_provider: AiProvider::Local,
             ai_mode: AiMode::Hybrid,
             pulse_speed_ms: 800,
             suggestion_delay_ms: 2000,
             auto_suggest: true,
+            cache_enabled: true,
+            cache_capacity: 256,
+            cache_on_disk: false,
+            model_prices: default_model_prices(),
+            monthly_budget: None,
+            offline_lock: false,
+            redaction: RedactionConfig::default(),
+            audit_log: false,
+            openai_base_url: "https://api.openai.com/v1".to_string(),
+            anthropic_base_url: "https://api.anthropic.com/v1".to_string(),
+            fixtures: None,
+            style_matching: true,
+            markov_model: "seed".to_string(),
+            markov_order: 3,
+            corpus_dir: None,
+            author_weight: 4,
+            dictionary: None,
+            seed: None,
+            markov_temperature: 1.0,
+            markov_weirdness: 0.1,
+            load_error: None,
         }
     }
 }
 
+/// Directory holding the config file and other persistent state
+/// (`$XDG_CONFIG_HOME/systymn` or `~/.config/systymn`)
+pub fn config_dir() -> Option<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = testing::CONFIG_DIR.with(|dir| dir.borrow().clone()) {
+        return Some(dir);
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("systymn"))
+}
+
 impl Config {
+    /// Load `config.json` from the config directory, falling back to defaults
+    /// for a missing file or missing fields. A file that can't be read or
+    /// parsed sets `load_error` instead. API keys not set in the file are
+    /// still picked up from the environment.
+    pub fn load() -> Self {
+        let Some(path) = config_dir().map(|dir| dir.join("config.json")) else {
+            return Self::default();
+        };
+        let mut config = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .unwrap_or_else(|e| Self::unusable(format!("{} is invalid: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => Self::unusable(format!("{} can't be read: {}", path.display(), e)),
+        };
+
+        if config.openai_api_key.is_none() {
+            config.openai_api_key = std::env::var("OPENAI_API_KEY").ok();
+        }
+        if config.anthropic_api_key.is_none() {
+            config.anthropic_api_key = std::env::var("ANTHROPIC_API_KEY").ok();
+        }
+        config
+    }
+
+    fn unusable(error: String) -> Self {
+        Self {
+            offline_lock: true,
+            load_error: Some(error),
+            ..Self::default()
+        }
+    }
+
     pub fn has_api_key(&self) -> bool {
         match self.ai_provider {
             AiProvider::Local => true,
@@ -112,19 +329,32 @@ impl Config {
         }
     }
 
+    /// Estimated cost in USD of a request, or zero for models missing from the price table
+    pub fn estimate_cost(&self, model: &str, usage: TokenUsage) -> f64 {
+        self.model_prices
+            .get(model)
+            .map(|price| {
+                (usage.input_tokens as f64 * price.input + usage.output_tokens as f64 * price.output)
+                    / 1_000_000.0
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// The configured entry for the provider's selected model, if it is listed
+    pub fn model_spec(&self, provider: AiProvider) -> Option<&ModelSpec> {
+        match provider {
+            AiProvider::Local => None,
+            AiProvider::OpenAI => self.openai_models.iter().find(|m| m.id == self.openai_model),
+            AiProvider::Anthropic => self.anthropic_models.iter().find(|m| m.id == self.anthropic_model),
+        }
+    }
+
     pub fn current_model_display(&self) -> &str {
         match self.ai_provider {
             AiProvider::Local => "Local Markov",
-            AiProvider::OpenAI => {
-                OPENAI_MODELS.get(self.openai_model_index)
-                    .map(|(_, name)| *name)
-                    .unwrap_or("GPT")
-            }
-            AiProvider::Anthropic => {
-                ANTHROPIC_MODELS.get(self.anthropic_model_index)
-                    .map(|(_, name)| *name)
-                    .unwrap_or("Claude")
-            }
+            provider => self.model_spec(provider)
+                .map(|m| m.name.as_str())
+                .unwrap_or_else(|| self.current_model()),
         }
     }
 
@@ -151,17 +381,31 @@ impl Config {
     }
 
     pub fn cycle_model(&mut self) {
-        match self.ai_provider {
-            AiProvider::Local => {}
-            AiProvider::OpenAI => {
-                self.openai_model_index = (self.openai_model_index + 1) % OPENAI_MODELS.len();
-                self.openai_model = OPENAI_MODELS[self.openai_model_index].0.to_string();
-            }
-            AiProvider::Anthropic => {
-                self.anthropic_model_index = (self.anthropic_model_index + 1) % ANTHROPIC_MODELS.len();
-                self.anthropic_model = ANTHROPIC_MODELS[self.anthropic_model_index].0.to_string();
-            }
+        let (models, current) = match self.ai_provider {
+            AiProvider::Local => return,
+            AiProvider::OpenAI => (&self.openai_models, &mut self.openai_model),
+            AiProvider::Anthropic => (&self.anthropic_models, &mut self.anthropic_model),
+        };
+        if models.is_empty() {
+            return;
         }
+
+        // A model missing from the list starts the cycle from the top
+        let next = models
+            .iter()
+            .position(|m| m.id == *current)
+            .map(|i| (i + 1) % models.len())
+            .unwrap_or(0);
+        *current = models[next].id.clone();
+    }
+
+    /// Step to the next temperature preset, wrapping from hottest to coolest
+    pub fn cycle_markov_temperature(&mut self) {
+        self.markov_temperature = next_preset(&[0.5, 0.8, 1.0, 1.5, 2.0], self.markov_temperature);
+    }
+
+    pub fn cycle_markov_weirdness(&mut self) {
+        self.markov_weirdness = next_preset(&[0.0, 0.1, 0.2, 0.4, 0.7], self.markov_weirdness);
     }
 
     pub fn cycle_mode(&mut self) {
@@ -176,4 +420,102 @@ impl Config {
     pub fn toggle_auto_suggest(&mut self) {
         self.auto_suggest = !self.auto_suggest;
     }
+
+    /// Stays locked while the config file is unusable
+    pub fn toggle_offline_lock(&mut self) {
+        if self.load_error.is_none() {
+            self.offline_lock = !self.offline_lock;
+        }
+    }
+}
+
+/// The first preset above `current`, or the lowest once past the last
+fn next_preset(presets: &[f32], current: f32) -> f32 {
+    presets
+        .iter()
+        .copied()
+        .find(|p| *p > current + f32::EPSILON)
+        .unwrap_or(presets[0])
+}
+
+#[cfg(test)]
+pub mod testing {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    thread_local! {
+        pub(super) static CONFIG_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    }
+
+    /// An empty config directory of a test's own, removed when dropped.
+    /// Tests run on parallel threads, so `config_dir` is overridden per thread.
+    pub struct TestConfigDir(PathBuf);
+
+    impl TestConfigDir {
+        pub fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "systymn-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            CONFIG_DIR.with(|current| *current.borrow_mut() = Some(dir.clone()));
+            Self(dir)
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TestConfigDir {
+        fn drop(&mut self) {
+            CONFIG_DIR.with(|current| *current.borrow_mut() = None);
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::TestConfigDir;
+    use super::*;
+
+    #[test]
+    fn unreadable_config_is_reported_and_stays_offline() {
+        let dir = TestConfigDir::new();
+        let config = Config::load();
+        assert!(config.load_error.is_none());
+        assert!(!config.offline_lock);
+
+        fs::write(dir.path().join("config.json"), r#"{"offline_lock": true, "monthly_budget": 5.0}"#).unwrap();
+        let config = Config::load();
+        assert!(config.load_error.is_none());
+        assert!(config.offline_lock);
+        assert_eq!(config.monthly_budget, Some(5.0));
+
+        fs::write(dir.path().join("config.json"), r#"{"monthly_budget": 5.0,}"#).unwrap();
+        let mut config = Config::load();
+        assert!(config.load_error.as_deref().unwrap().contains("config.json is invalid"));
+        assert!(config.offline_lock);
+        config.toggle_offline_lock();
+        assert!(config.offline_lock);
+    }
+
+    #[test]
+    fn price_overrides_keep_the_other_defaults() {
+        let config: Config = serde_json::from_str(
+            r#"{"model_prices": {"gpt-4o": {"input": 1.0, "output": 2.0}, "my-model": {"input": 3.0, "output": 4.0}}}"#,
+        )
+        .unwrap();
+        let defaults = default_model_prices();
+        assert_eq!(config.model_prices["gpt-4o"], ModelPrice { input: 1.0, output: 2.0 });
+        assert_eq!(config.model_prices["my-model"], ModelPrice { input: 3.0, output: 4.0 });
+        assert_eq!(config.model_prices["gpt-4o-mini"], defaults["gpt-4o-mini"]);
+        assert_eq!(config.model_prices.len(), defaults.len() + 1);
+    }
 }
@@ -95,69 +95,67 @@ impl EmojiEngine {
         Self { categories, mood_emojis }
     }
 
-    pub fn random_emoji(&self) -> &str {
-        let mut rng = thread_rng();
-        let all_categories: Vec<_> = self.categories.values().collect();
-        if let Some(category) = all_categories.choose(&mut rng) {
-            if let Some(emoji) = category.choose(&mut rng) {
+    pub fn random_emoji(&self, rng: &mut impl Rng) -> &str {
+        // Categories in a fixed order, so a seeded RNG picks the same one
+        let all_categories: Vec<_> = self.all_categories().into_iter().filter_map(|c| self.categories.get(c)).collect();
+        if let Some(category) = all_categories.choose(rng) {
+            if let Some(emoji) = category.choose(rng) {
                 return emoji;
             }
         }
         "\u{2728}" // sparkles as fallback
     }
 
-    pub fn emoji_by_category(&self, category: &str) -> Option<&str> {
-        let mut rng = thread_rng();
+    pub fn emoji_by_category(&self, category: &str, rng: &mut impl Rng) -> Option<&str> {
         self.categories
             .get(category)
-            .and_then(|emojis| emojis.choose(&mut rng).copied())
+            .and_then(|emojis| emojis.choose(rng).copied())
     }
 
-    pub fn emoji_for_mood(&self, mood: &str) -> Option<&str> {
-        let mut rng = thread_rng();
+    pub fn emoji_for_mood(&self, mood: &str, rng: &mut impl Rng) -> Option<&str> {
         self.mood_emojis
             .get(mood)
-            .and_then(|emojis| emojis.choose(&mut rng).copied())
+            .and_then(|emojis| emojis.choose(rng).copied())
     }
 
-    pub fn suggest_emoji(&self, text: &str) -> Option<&str> {
+    pub fn suggest_emoji(&self, text: &str, rng: &mut impl Rng) -> Option<&str> {
         let lower = text.to_lowercase();
 
         // Detect mood from text
         if lower.contains("happy") || lower.contains("joy") || lower.contains("great") || lower.contains("wonderful") {
-            return self.emoji_for_mood("happy");
+            return self.emoji_for_mood("happy", rng);
         }
         if lower.contains("sad") || lower.contains("cry") || lower.contains("tears") || lower.contains("miss") {
-            return self.emoji_for_mood("sad");
+            return self.emoji_for_mood("sad", rng);
         }
         if lower.contains("love") || lower.contains("heart") || lower.contains("adore") || lower.contains("dear") {
-            return self.emoji_for_mood("love");
+            return self.emoji_for_mood("love", rng);
         }
         if lower.contains("angry") || lower.contains("hate") || lower.contains("furious") || lower.contains("mad") {
-            return self.emoji_for_mood("angry");
+            return self.emoji_for_mood("angry", rng);
         }
         if lower.contains("think") || lower.contains("wonder") || lower.contains("hmm") || lower.contains("question") {
-            return self.emoji_for_mood("think");
+            return self.emoji_for_mood("think", rng);
         }
         if lower.contains("cool") || lower.contains("awesome") || lower.contains("amazing") || lower.contains("epic") {
-            return self.emoji_for_mood("cool");
+            return self.emoji_for_mood("cool", rng);
         }
 
         // Detect categories from text
         if lower.contains("cat") || lower.contains("dog") || lower.contains("animal") || lower.contains("bird") {
-            return self.emoji_by_category("animal");
+            return self.emoji_by_category("animal", rng);
         }
         if lower.contains("tree") || lower.contains("flower") || lower.contains("nature") || lower.contains("forest") {
-            return self.emoji_by_category("nature");
+            return self.emoji_by_category("nature", rng);
         }
         if lower.contains("rain") || lower.contains("sun") || lower.contains("cloud") || lower.contains("weather") {
-            return self.emoji_by_category("weather");
+            return self.emoji_by_category("weather", rng);
         }
         if lower.contains("food") || lower.contains("eat") || lower.contains("hungry") || lower.contains("delicious") {
-            return self.emoji_by_category("food");
+            return self.emoji_by_category("food", rng);
         }
         if lower.contains("magic") || lower.contains("sparkle") || lower.contains("shine") || lower.contains("star") {
-            return self.emoji_by_category("magic");
+            return self.emoji_by_category("magic", rng);
         }
 
         None
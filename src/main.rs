@@ -8,6 +8,7 @@ mod emoji;
 mod search;
 
 use std::io;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
@@ -18,10 +19,49 @@ use ratatui::prelude::*;
 use ratatui::Terminal;
 use tokio::sync::mpsc;
 
+use ai::{model_path, MarkovEngine};
+#[cfg(feature = "fixtures")]
+use ai::MockServer;
 use app::App;
+use config::Config;
+
+const BUILD_MODEL_USAGE: &str = "usage: systymn build-model <name or file.json> <corpus file or dir>... [--order N]";
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "build-model") {
+        return build_model(&args[1..]);
+    }
+    let seed = seed_arg(&args).unwrap_or_else(|error| {
+        eprintln!("{}\nusage: systymn [--seed N]", error);
+        std::process::exit(2);
+    });
+
+    let mut config = Config::load();
+    if let Some(error) = &config.load_error {
+        eprintln!("Warning: {}; using defaults with API calls locked offline", error);
+    }
+    if seed.is_some() {
+        config.seed = seed;
+    }
+
+    // In fixture mode all API traffic goes through the in-process mock server,
+    // which has to stay alive for as long as the app runs
+    #[cfg(feature = "fixtures")]
+    let _mock_server = match config.fixtures.clone() {
+        Some(fixtures) => {
+            let server = MockServer::start_with_fixtures(&fixtures, &config).await?;
+            server.configure(&mut config);
+            Some(server)
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "fixtures"))]
+    if config.fixtures.is_some() {
+        eprintln!("Ignoring \"fixtures\" in config.json: this build has no mock server (build with --features fixtures)");
+    }
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -29,8 +69,10 @@ async fn main() -> io::Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     let (api_tx, api_rx) = mpsc::channel(10);
-    let app = App::new(api_tx);
-    let res = run_app(&mut terminal, app, api_rx).await;
+    let mut app = App::new(config, api_tx);
+    let res = run_app(&mut terminal, &mut app, api_rx).await;
+    // The periodic save misses whatever happened in the last few seconds
+    app.analytics.save();
 
     disable_raw_mode()?;
     execute!(
@@ -47,16 +89,76 @@ async fn main() -> io::Result<()> {
     Ok(())
 }
 
+/// `--seed N` or `--seed=N`, overriding the seed in the config file
+fn seed_arg(args: &[String]) -> Result<Option<u64>, String> {
+    let Some(i) = args.iter().position(|arg| arg == "--seed" || arg.starts_with("--seed=")) else {
+        return Ok(None);
+    };
+    let value = match args[i].strip_prefix("--seed=") {
+        Some(value) => value,
+        None => args.get(i + 1).map(String::as_str).unwrap_or_default(),
+    };
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("--seed needs a whole number, not '{}'", value))
+}
+
+/// Train a Markov model on a corpus and save it, either by name into the
+/// models directory or to an explicit `.json` path
+fn build_model(args: &[String]) -> io::Result<()> {
+    let mut order = ai::DEFAULT_MARKOV_ORDER;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--order" {
+            match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => order = n,
+                None => {
+                    eprintln!("{}", BUILD_MODEL_USAGE);
+                    std::process::exit(2);
+                }
+            }
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    let Some((name, corpus)) = positional.split_first().filter(|(_, corpus)| !corpus.is_empty()) else {
+        eprintln!("{}", BUILD_MODEL_USAGE);
+        std::process::exit(2);
+    };
+    let output = if name.ends_with(".json") {
+        Some(PathBuf::from(name))
+    } else {
+        model_path(name)
+    };
+    let Some(output) = output else {
+        eprintln!("No config directory to save models in; give an explicit .json path");
+        std::process::exit(1);
+    };
+
+    let corpus: Vec<PathBuf> = corpus.iter().map(PathBuf::from).collect();
+    let (model, files) = MarkovEngine::from_corpus(&corpus, order);
+    if files == 0 {
+        eprintln!("No .txt or .md files found in the corpus");
+        std::process::exit(1);
+    }
+    model.save(&output)?;
+    println!("Built an order {} model from {} file(s): {}", model.order(), files, output.display());
+    Ok(())
+}
+
 async fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
-    mut app: App,
+    app: &mut App,
     mut api_rx: mpsc::Receiver<ai::ApiResponse>,
 ) -> io::Result<()> {
     let tick_rate = Duration::from_millis(50);
     let mut last_tick = Instant::now();
 
     loop {
-        terminal.draw(|f| ui::draw(f, &mut app))?;
+        terminal.draw(|f| ui::draw(f, app))?;
 
         // Check for API responses (non-blocking)
         while let Ok(response) = api_rx.try_recv() {
@@ -82,6 +184,30 @@ async fn run_app<B: Backend>(
                         (_, KeyCode::Char(c)) => app.search_add_char(c),
                         _ => {}
                     }
+                } else if app.audit_viewer.is_active {
+                    match (key.modifiers, key.code) {
+                        (KeyModifiers::CONTROL, KeyCode::Char('q')) => return Ok(()),
+                        (_, KeyCode::Esc) | (_, KeyCode::F(6)) => app.toggle_audit_viewer(),
+                        (_, KeyCode::Up) => app.audit_viewer.up(),
+                        (_, KeyCode::Down) => app.audit_viewer.down(),
+                        (_, KeyCode::Left) => app.audit_viewer.prev_filter(),
+                        (_, KeyCode::Right) | (_, KeyCode::Tab) => app.audit_viewer.next_filter(),
+                        _ => {}
+                    }
+                } else if app.chat.is_active && app.chat.focused {
+                    match (key.modifiers, key.code) {
+                        (KeyModifiers::CONTROL, KeyCode::Char('q')) => return Ok(()),
+                        (KeyModifiers::CONTROL, KeyCode::Char('k')) => app.chat.clear(),
+                        (_, KeyCode::Esc) => app.chat.unfocus(),
+                        (_, KeyCode::F(2)) => app.toggle_chat(),
+                        (_, KeyCode::Enter) => app.send_chat_message(),
+                        (_, KeyCode::Tab) => app.insert_chat_reply(),
+                        (_, KeyCode::Backspace) => app.chat.backspace(),
+                        (_, KeyCode::Up) | (_, KeyCode::PageUp) => app.chat.scroll_up(),
+                        (_, KeyCode::Down) | (_, KeyCode::PageDown) => app.chat.scroll_down(),
+                        (_, KeyCode::Char(c)) => app.chat.add_char(c),
+                        _ => {}
+                    }
                 } else {
                     match (key.modifiers, key.code) {
                         (KeyModifiers::CONTROL, KeyCode::Char('q')) => return Ok(()),
@@ -93,10 +219,13 @@ async fn run_app<B: Backend>(
                         (KeyModifiers::CONTROL, KeyCode::Char('w')) => app.save_file(),
                         (KeyModifiers::CONTROL, KeyCode::Char('e')) => app.toggle_emoji_mode(),
                         (KeyModifiers::CONTROL, KeyCode::Char('g')) => app.fetch_api_suggestion(),
+                        (KeyModifiers::CONTROL, KeyCode::Char('d')) => app.fetch_api_sentence(),
+                        (KeyModifiers::CONTROL, KeyCode::Char('r')) => app.toggle_critique(),
                         (KeyModifiers::CONTROL, KeyCode::Char('p')) => app.cycle_ai_provider(),
                         (KeyModifiers::CONTROL, KeyCode::Char('m')) => app.cycle_ai_model(),
                         (KeyModifiers::CONTROL, KeyCode::Char('n')) => app.cycle_ai_mode(),
                         (KeyModifiers::CONTROL, KeyCode::Char('t')) => app.toggle_auto_suggest(),
+                        (KeyModifiers::CONTROL, KeyCode::Char('l')) => app.toggle_offline_lock(),
                         (KeyModifiers::CONTROL, KeyCode::Char('z')) => app.undo(),
                         (KeyModifiers::CONTROL, KeyCode::Char('y')) => app.redo(),
                         (KeyModifiers::CONTROL, KeyCode::Char('c')) => app.copy(),
@@ -106,7 +235,17 @@ async fn run_app<B: Backend>(
                         (KeyModifiers::CONTROL, KeyCode::Char('f')) => app.open_search(),
                         (KeyModifiers::CONTROL, KeyCode::Char('h')) => app.open_replace(),
                         (_, KeyCode::F(1)) => app.toggle_help(),
+                        (_, KeyCode::F(2)) => app.toggle_chat(),
                         (_, KeyCode::F(3)) => app.search_next(),
+                        (_, KeyCode::F(4)) => app.toggle_cast(),
+                        (_, KeyCode::F(5)) => app.summarize_chapters(),
+                        (_, KeyCode::F(6)) => app.toggle_audit_viewer(),
+                        (_, KeyCode::F(7)) => app.toggle_stats(),
+                        (_, KeyCode::F(8)) => app.cycle_markov_model(),
+                        (_, KeyCode::F(9)) => app.cycle_markov_temperature(),
+                        (_, KeyCode::F(10)) => app.cycle_markov_weirdness(),
+                        (_, KeyCode::Up) if app.command_menu.is_some() => app.command_menu_up(),
+                        (_, KeyCode::Down) if app.command_menu.is_some() => app.command_menu_down(),
                         (_, KeyCode::Tab) => app.accept_suggestion(),
                         (_, KeyCode::Esc) => app.dismiss_or_exit(),
                         (_, KeyCode::Enter) => app.handle_enter(),
@@ -116,6 +255,7 @@ async fn run_app<B: Backend>(
                         (KeyModifiers::SHIFT, KeyCode::Right) => app.select_right(),
                         (KeyModifiers::SHIFT, KeyCode::Up) => app.select_up(),
                         (KeyModifiers::SHIFT, KeyCode::Down) => app.select_down(),
+                        (KeyModifiers::CONTROL, KeyCode::Right) => app.accept_next_word(),
                         (_, KeyCode::Left) => app.move_cursor_left(),
                         (_, KeyCode::Right) => app.move_cursor_right(),
                         (_, KeyCode::Up) => app.move_cursor_up(),
@@ -139,3 +279,22 @@ async fn run_app<B: Backend>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn seed_is_taken_only_from_the_seed_flag() {
+        assert_eq!(seed_arg(&args("--seed 1996")), Ok(Some(1996)));
+        assert_eq!(seed_arg(&args("novel.md --seed=7")), Ok(Some(7)));
+        assert_eq!(seed_arg(&args("--seedling 3")), Ok(None));
+        assert!(seed_arg(&args("--seed abc")).is_err());
+        assert!(seed_arg(&args("--seed=-1")).is_err());
+        assert!(seed_arg(&args("--seed")).is_err());
+    }
+}
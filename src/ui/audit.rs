@@ -0,0 +1,89 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+
+use crate::app::App;
+
+pub fn draw_audit_popup(frame: &mut Frame, app: &App) {
+    let area = super::centered_rect(80, 80, frame.area());
+    frame.render_widget(Clear, area);
+
+    let viewer = &app.audit_viewer;
+    let records = viewer.visible();
+
+    let block = Block::default()
+        .title(format!(" Audit log: {} ({}) ", viewer.filter_label(), records.len()))
+        .title_bottom(" \u{2190}/\u{2192}:Document | \u{2191}/\u{2193}:Select | Esc:Close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    if records.is_empty() {
+        let empty = Paragraph::new("  No requests recorded")
+            .style(Style::default().fg(Color::Gray));
+        frame.render_widget(empty, inner);
+        return;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+        .split(inner);
+
+    let items: Vec<ListItem> = records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            let tokens = record.usage
+                .map(|u| format!("{}+{} tok", u.input_tokens, u.output_tokens))
+                .unwrap_or_else(|| "failed".to_string());
+            let line = format!(
+                " {}  {:<9} {:<28} {:>14} {:>6}ms",
+                record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                record.provider.to_string(),
+                record.model,
+                tokens,
+                record.latency_ms,
+            );
+            let style = if i == viewer.selected {
+                Style::default().fg(Color::Black).bg(Color::Cyan)
+            } else if record.error.is_some() {
+                Style::default().fg(Color::Red)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            ListItem::new(line).style(style)
+        })
+        .collect();
+
+    let mut state = ListState::default();
+    state.select(Some(viewer.selected));
+    frame.render_stateful_widget(List::new(items), chunks[0], &mut state);
+
+    if let Some(record) = records.get(viewer.selected) {
+        let label = Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD);
+        let mut lines = vec![Line::from(vec![
+            Span::styled("Document: ", label),
+            Span::raw(record.document.clone().unwrap_or_else(|| "(unsaved)".to_string())),
+        ])];
+        if let Some(system) = &record.system {
+            lines.push(Line::from(Span::styled("System:", label)));
+            lines.push(Line::from(system.clone()));
+        }
+        lines.push(Line::from(Span::styled("Prompt:", label)));
+        lines.extend(record.prompt.lines().map(|l| Line::from(l.to_string())));
+        lines.push(Line::from(Span::styled("Response:", label)));
+        match (&record.response, &record.error) {
+            (Some(response), _) => lines.extend(response.lines().map(|l| Line::from(l.to_string()))),
+            (None, Some(error)) => lines.push(Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red)))),
+            (None, None) => {}
+        }
+
+        let details = Paragraph::new(lines)
+            .style(Style::default().fg(Color::White))
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::TOP).border_style(Style::default().fg(Color::DarkGray)));
+        frame.render_widget(details, chunks[1]);
+    }
+}
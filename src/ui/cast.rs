@@ -0,0 +1,51 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::app::App;
+use crate::commands::CastKind;
+
+pub fn draw_cast_popup(frame: &mut Frame, app: &App) {
+    let area = super::centered_rect(60, 70, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Cast (F4) ")
+        .title_bottom(" /cast name re-inserts | --role=... sets a role | Esc:Close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let members = app.cast.members();
+    if members.is_empty() {
+        let empty = Paragraph::new("  Nobody yet - names, cities and objects from /n, /c and /o appear here")
+            .style(Style::default().fg(Color::Gray));
+        frame.render_widget(empty, inner);
+        return;
+    }
+
+    let mut lines = Vec::new();
+    for kind in [CastKind::Character, CastKind::Place, CastKind::Object] {
+        let of_kind: Vec<_> = members.iter().filter(|m| m.kind == kind).collect();
+        if of_kind.is_empty() {
+            continue;
+        }
+        if !lines.is_empty() {
+            lines.push(Line::default());
+        }
+        lines.push(Line::from(Span::styled(
+            format!(" {}s ({})", kind.label(), of_kind.len()),
+            Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+        )));
+        for member in of_kind {
+            let mut spans = vec![Span::styled(format!("  {}", member.name), Style::default().fg(Color::White))];
+            if let Some(role) = &member.role {
+                spans.push(Span::styled(format!("  {}", role), Style::default().fg(Color::Yellow)));
+            }
+            lines.push(Line::from(spans));
+        }
+    }
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
@@ -0,0 +1,82 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Paragraph};
+use unicode_width::UnicodeWidthStr;
+
+use crate::ai::ChatRole;
+use crate::app::App;
+use crate::buffer::TextBuffer;
+
+pub fn draw_chat_panel(frame: &mut Frame, app: &App, area: Rect) {
+    let chat = &app.chat;
+    let border = if chat.focused { Color::Cyan } else { Color::DarkGray };
+
+    let block = Block::default()
+        .title(format!(" Chat: {} ", app.config.current_model_display()))
+        .title_bottom(" Enter:Send | Tab:Insert | Ctrl+K:Clear | Esc:Editor ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(border));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(inner);
+
+    // Wrap by hand so the view can stay pinned to the newest message
+    let width = chunks[0].width as usize;
+    let mut lines: Vec<Line> = Vec::new();
+    for message in &chat.messages {
+        let (label, color) = match message.role {
+            ChatRole::User => ("You", Color::Yellow),
+            ChatRole::Assistant => ("AI", Color::Cyan),
+        };
+        lines.push(Line::from(Span::styled(
+            format!("{}:", label),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        )));
+        for line in message.content.lines() {
+            for segment in TextBuffer::wrap_line(line, width) {
+                lines.push(Line::from(segment));
+            }
+        }
+        lines.push(Line::default());
+    }
+    if chat.loading {
+        lines.push(Line::from(Span::styled("thinking \u{23F3}", Style::default().fg(Color::Gray))));
+    } else if chat.messages.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "Ask about the selection or the current chapter",
+            Style::default().fg(Color::Gray),
+        )));
+    }
+
+    let height = chunks[0].height as usize;
+    let max_scroll = lines.len().saturating_sub(height);
+    let top = max_scroll - chat.scroll.min(max_scroll);
+    let history = Paragraph::new(lines)
+        .style(Style::default().fg(Color::White))
+        .scroll((top as u16, 0));
+    frame.render_widget(history, chunks[0]);
+
+    // Show the tail of the input when it's wider than the panel
+    let prompt = "> ";
+    let available = width.saturating_sub(prompt.len() + 1);
+    let mut input = chat.input.as_str();
+    while input.width() > available {
+        let mut chars = input.chars();
+        chars.next();
+        input = chars.as_str();
+    }
+    let input_line = Paragraph::new(format!("{}{}", prompt, input))
+        .style(Style::default().fg(Color::White))
+        .block(Block::default().borders(Borders::TOP).border_style(Style::default().fg(Color::DarkGray)));
+    frame.render_widget(input_line, chunks[1]);
+
+    if chat.focused {
+        frame.set_cursor_position(Position::new(
+            chunks[1].x + (prompt.len() + input.width()) as u16,
+            chunks[1].y + 1,
+        ));
+    }
+}
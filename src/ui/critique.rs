@@ -0,0 +1,51 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::ai::{Annotation, CritiqueKind};
+use crate::buffer::TextBuffer;
+
+const POPUP_WIDTH: u16 = 48;
+
+pub fn kind_color(kind: CritiqueKind) -> Color {
+    match kind {
+        CritiqueKind::Pacing => Color::Yellow,
+        CritiqueKind::Cliche => Color::Magenta,
+        CritiqueKind::Unclear => Color::Red,
+        CritiqueKind::Other => Color::Cyan,
+    }
+}
+
+/// Explain the annotation under the cursor, just below the cursor line when
+/// there's room and above it otherwise
+pub fn draw_annotation_popup(frame: &mut Frame, annotation: &Annotation, cursor: Position, bounds: Rect) {
+    let width = POPUP_WIDTH.min(bounds.width);
+    let text_width = width.saturating_sub(2) as usize;
+    let note: Vec<Line> = TextBuffer::wrap_line(&annotation.note, text_width)
+        .into_iter()
+        .map(Line::from)
+        .collect();
+    let height = (note.len() as u16 + 2).min(bounds.height);
+
+    let below = cursor.y + 1;
+    let y = if below + height <= bounds.y + bounds.height {
+        below
+    } else {
+        cursor.y.saturating_sub(height).max(bounds.y)
+    };
+    let x = cursor.x.min(bounds.x + bounds.width - width);
+    let area = Rect::new(x, y, width, height);
+
+    let color = kind_color(annotation.kind);
+    let popup = Paragraph::new(note)
+        .style(Style::default().fg(Color::White))
+        .block(
+            Block::default()
+                .title(format!(" {} ", annotation.kind))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(color))
+                .style(Style::default().bg(Color::Black)),
+        );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
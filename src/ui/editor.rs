@@ -3,6 +3,7 @@ use ratatui::widgets::{Block, Borders, Paragraph};
 use unicode_width::UnicodeWidthStr;
 
 use crate::app::App;
+use super::{critique, menu};
 
 pub fn draw_editor(frame: &mut Frame, app: &mut App, area: Rect) {
     let inner = Block::default()
@@ -57,6 +58,12 @@ pub fn draw_editor(frame: &mut Frame, app: &mut App, area: Rect) {
         let apply_highlighting = |text: &str, line_idx: usize, col_start: usize| -> Vec<Span> {
             let mut result: Vec<Span> = Vec::new();
             let text_chars: Vec<char> = text.chars().collect();
+            // Critique notes are anchored to byte ranges of the line
+            let notes = app.critique.on_line(app.buffer.lines(), line_idx);
+            let byte_cols: Vec<usize> = text.char_indices().map(|(b, _)| col_start + b).collect();
+            let note_at = |i: usize| {
+                notes.iter().copied().find(|a| byte_cols[i] >= a.start && byte_cols[i] < a.end)
+            };
             let mut i = 0;
 
             while i < text_chars.len() {
@@ -105,7 +112,8 @@ pub fn draw_editor(frame: &mut Frame, app: &mut App, area: Rect) {
                     result.push(Span::styled(sel_text, Style::default().bg(Color::Blue).fg(Color::White)));
                     i = sel_end;
                 } else {
-                    // Regular text - collect until next highlight
+                    // Regular text, underlined under a critique note - collect until next highlight
+                    let annotation = note_at(i);
                     let mut plain_end = i + 1;
                     while plain_end < text_chars.len() {
                         let check_col = col_start + plain_end;
@@ -123,13 +131,28 @@ pub fn draw_editor(frame: &mut Frame, app: &mut App, area: Rect) {
                             false
                         };
 
-                        if is_search || is_sel {
+                        // Check if the next char starts or ends a critique note
+                        let same_note = match (annotation, note_at(plain_end)) {
+                            (Some(a), Some(b)) => std::ptr::eq(a, b),
+                            (None, None) => true,
+                            _ => false,
+                        };
+
+                        if is_search || is_sel || !same_note {
                             break;
                         }
                         plain_end += 1;
                     }
                     let plain_text: String = text_chars[i..plain_end].iter().collect();
-                    result.push(Span::raw(plain_text));
+                    match annotation {
+                        Some(note) => result.push(Span::styled(
+                            plain_text,
+                            Style::default()
+                                .add_modifier(Modifier::UNDERLINED)
+                                .underline_color(critique::kind_color(note.kind)),
+                        )),
+                        None => result.push(Span::raw(plain_text)),
+                    }
                     i = plain_end;
                 }
             }
@@ -162,10 +185,13 @@ pub fn draw_editor(frame: &mut Frame, app: &mut App, area: Rect) {
                     let intensity = ((pulse * std::f32::consts::PI * 2.0).sin() * 0.5 + 0.5) * 0.4 + 0.3;
                     let blue_level = (intensity * 255.0) as u8;
 
-                    spans.push(Span::styled(
-                        suggestion.text.clone(),
-                        Style::default().fg(Color::Rgb(100, 150, blue_level + 100))
-                    ));
+                    // Cached completions get a green tint so they read as instant replays
+                    let color = if suggestion.cached {
+                        Color::Rgb(100, blue_level + 100, 150)
+                    } else {
+                        Color::Rgb(100, 150, blue_level + 100)
+                    };
+                    spans.push(Span::styled(suggestion.text.clone(), Style::default().fg(color)));
                 } else if let Some(suggestion) = &app.current_suggestion {
                     let pulse = app.pulse_phase;
                     let intensity = ((pulse * std::f32::consts::PI * 2.0).sin() * 0.5 + 0.5) * 0.4 + 0.3;
@@ -245,8 +271,19 @@ pub fn draw_editor(frame: &mut Frame, app: &mut App, area: Rect) {
 
     // Position cursor (accounting for scroll)
     let screen_cursor_y = visual_cursor_y.saturating_sub(scroll_offset);
-    frame.set_cursor_position(Position::new(
+    let cursor = Position::new(
         inner.x + visual_cursor_x as u16,
         inner.y + screen_cursor_y as u16,
-    ));
+    );
+    frame.set_cursor_position(cursor);
+
+    if let Some(command_menu) = &app.command_menu {
+        menu::draw_command_menu(frame, command_menu, cursor, inner);
+        return;
+    }
+
+    // Explain the critique note under the cursor
+    if let Some(annotation) = app.critique.at(app.buffer.lines(), cursor_y, cursor_x) {
+        critique::draw_annotation_popup(frame, annotation, cursor, inner);
+    }
 }
@@ -0,0 +1,64 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use unicode_width::UnicodeWidthStr;
+
+use crate::commands::CommandMenu;
+
+const POPUP_WIDTH: u16 = 72;
+const MAX_ROWS: usize = 8;
+
+/// The command dropdown, under the cursor if it fits and above it if not
+pub fn draw_command_menu(frame: &mut Frame, menu: &CommandMenu, cursor: Position, bounds: Rect) {
+    let width = POPUP_WIDTH.min(bounds.width);
+    let rows = menu.items.len().min(MAX_ROWS);
+    let height = (rows as u16 + 2).min(bounds.height);
+
+    let below = cursor.y + 1;
+    let y = if below + height <= bounds.y + bounds.height {
+        below
+    } else {
+        cursor.y.saturating_sub(height).max(bounds.y)
+    };
+    let x = cursor.x.saturating_sub(1).max(bounds.x).min(bounds.x + bounds.width - width);
+    let area = Rect::new(x, y, width, height);
+
+    // Keep the selection in view
+    let first = (menu.selected + 1).saturating_sub(rows);
+    let name_width = menu.items.iter().map(|item| item.name.width() + 1).max().unwrap_or(0);
+    let text_width = width.saturating_sub(2) as usize;
+    let lines: Vec<Line> = menu
+        .items
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(rows)
+        .map(|(i, item)| {
+            let selected = i == menu.selected;
+            let base = if selected {
+                Style::default().fg(Color::Black).bg(Color::Cyan)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let name = format!(" /{:<width$} ", item.name, width = name_width);
+            let description = format!("{}  ", item.description);
+            let room = text_width.saturating_sub(name.width() + description.width());
+            let sample: String = item.sample.chars().take(room).collect();
+            Line::from(vec![
+                Span::styled(name, base.add_modifier(Modifier::BOLD)),
+                Span::styled(description, base),
+                Span::styled(sample, base.fg(if selected { Color::Black } else { Color::Yellow }).add_modifier(Modifier::ITALIC)),
+            ])
+        })
+        .collect();
+
+    let popup = Paragraph::new(lines).block(
+        Block::default()
+            .title_bottom(" Up/Down  Tab:complete  Enter:run ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .style(Style::default().bg(Color::Black)),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(popup, area);
+}
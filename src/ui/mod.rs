@@ -1,3 +1,9 @@
+mod audit;
+mod cast;
+mod chat;
+mod critique;
+mod menu;
+mod stats;
 mod editor;
 mod suggestions;
 pub mod synonyms;
@@ -28,7 +34,17 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
             .split(frame.area())
     };
 
-    editor::draw_editor(frame, app, chunks[0]);
+    // The chat pane takes the right side of the editor area while open
+    if app.chat.is_active {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(chunks[0]);
+        editor::draw_editor(frame, app, columns[0]);
+        chat::draw_chat_panel(frame, app, columns[1]);
+    } else {
+        editor::draw_editor(frame, app, chunks[0]);
+    }
 
     if app.search.is_active {
         draw_search_bar(frame, app, chunks[1]);
@@ -41,6 +57,18 @@ pub fn draw(frame: &mut Frame, app: &mut App) {
         synonyms::draw_synonym_popup(frame, app);
     }
 
+    if app.audit_viewer.is_active {
+        audit::draw_audit_popup(frame, app);
+    }
+
+    if app.show_stats {
+        stats::draw_stats_popup(frame, app);
+    }
+
+    if app.show_cast {
+        cast::draw_cast_popup(frame, app);
+    }
+
     if app.show_help {
         draw_help_popup(frame);
     }
@@ -86,9 +114,11 @@ fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     let (x, y) = app.buffer.cursor();
 
     // Build mode indicator
-    let mode = if app.show_synonyms {
+    let mode = if app.chat.focused {
+        "CHAT"
+    } else if app.show_synonyms {
         "SYNONYM"
-    } else if app.command_preview.is_some() {
+    } else if app.command_preview.is_some() || app.command_menu.is_some() {
         "COMMAND"
     } else if app.emoji_mode {
         "EMOJI"
@@ -99,6 +129,8 @@ fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     // AI status with provider, mode, and model info
     let ai_status = if app.api_loading {
         format!("{} \u{23F3}", app.config.ai_mode)
+    } else if let Some(reason) = app.api_block_reason() {
+        format!("{} | Local [{}]", app.config.ai_mode, reason)
     } else {
         format!("{} | {} [{}]",
             app.config.ai_mode,
@@ -106,6 +138,27 @@ fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
             app.config.current_model_display()
         )
     };
+    let sampling = app.sampling();
+    let ai_status = format!(
+        "{} | Markov: {} t{:.1} w{:.1}",
+        ai_status,
+        app.markov_model(),
+        sampling.temperature,
+        sampling.weirdness
+    );
+
+    // Token spend, once anything has been spent this session
+    let session_usage = app.usage.session();
+    let ai_status = if session_usage.requests > 0 {
+        format!(
+            "{} | Session {} (doc ${:.4})",
+            ai_status,
+            session_usage.summary(),
+            app.usage.document().cost
+        )
+    } else {
+        ai_status
+    };
 
     // Word and character count
     let word_count = app.word_count();
@@ -141,8 +194,12 @@ fn draw_status_bar(frame: &mut Frame, app: &App, area: Rect) {
     // Second line shows quick help hints
     let hints = if app.search.is_active {
         " Enter:Next | Shift+Enter:Prev | Ctrl+Enter:Replace | Esc:Close "
+    } else if app.chat.focused {
+        " Enter:Send | Tab:Insert reply at cursor | Up/Down:Scroll | Esc:Back to editor | F2:Close chat "
     } else if app.emoji_mode {
         " Tab:Accept | Ctrl+E:Exit Emoji | Ctrl+G:AI | Ctrl+Space:Sentence "
+    } else if app.api_suggestion.as_ref().is_some_and(|s| s.cached) {
+        " AI ready (cached) - Tab:Accept | Ctrl+Space:Sentence | Ctrl+P:Provider | Ctrl+M:Model "
     } else if app.api_suggestion.is_some() {
         " AI ready - Tab:Accept | Ctrl+Space:Sentence | Ctrl+P:Provider | Ctrl+M:Model "
     } else {
@@ -195,12 +252,30 @@ fn draw_help_popup(frame: &mut Frame) {
 
   AI SUGGESTIONS
     Tab              Accept word suggestion
+    Ctrl+Right       Accept only the next word
     Ctrl+Space       Accept sentence suggestion
     Ctrl+G           Fetch AI suggestion
+    Ctrl+D           Fetch AI sentence
     Ctrl+P           Cycle AI provider (Local/OpenAI/Anthropic)
     Ctrl+M           Cycle AI model
     Ctrl+N           Cycle AI mode (Off/Local/API/Hybrid)
     Ctrl+T           Toggle auto-suggestions
+    Ctrl+L           Toggle offline lock (no network requests)
+    F6               Audit log viewer (Left/Right: filter by document)
+    Ctrl+R           Critique the text on screen (again to clear)
+    F4               Cast of generated names, places and objects
+    F5               Summarize changed chapters (used as long-range context)
+    F7               Suggestion acceptance stats
+    F8               Switch Markov model
+    F9               Cycle Markov temperature (sentence sampling)
+    F10              Cycle Markov weirdness (unlikely turns)
+
+  CHAT
+    F2               Open/focus/close the chat panel
+    Enter            Ask about the selection or current chapter
+    Tab              Insert the latest reply at the cursor
+    Ctrl+K           Clear the conversation
+    Esc              Back to the editor (panel stays open)
 
   SYNONYMS
     Ctrl+S           Open synonym selector
@@ -209,9 +284,10 @@ fn draw_help_popup(frame: &mut Frame) {
 
   EMOJI MODE
     Ctrl+E           Toggle emoji mode
-    /emoji [cat]     Insert emoji (face, nature, animal, heart, food)
+    /emoji:cat       Insert emoji (face, nature, animal, heart, food)
 
   GENERATOR COMMANDS
+    /                Command menu (Up/Down, Tab completes, Enter runs)
     /n               Random name (/nm male, /nf female, /nx neutral)
     /c               Random city
     /l               Location (/li interior, /lo outdoor)
@@ -219,6 +295,12 @@ fn draw_help_popup(frame: &mut Frame) {
     /o               Random object
     /t               Time (/td day, /tn night)
     /a               Action verb
+    /n:f 3           Variants, counts (3 or x3), and/or, --options
+    /elf             Invented name (/norse, /latin; --syl=N --len=MIN-MAX)
+    /<list>          Your own lists from ~/.config/systymn/generators
+    /tpl name        Expand a template (prompt, scene, quest, your own)
+    /cast name       Re-insert someone generated before (Tab completes)
+    --role=...       Give a generated name a role in the cast
 
   FILES
     Ctrl+O           Open file
@@ -0,0 +1,77 @@
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::ai::SuggestionSource;
+use crate::app::App;
+
+pub fn draw_stats_popup(frame: &mut Frame, app: &App) {
+    let area = super::centered_rect(80, 70, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Suggestion stats (F7) ")
+        .title_bottom(" Ctrl+Right accepts one word (partial) | Esc:Close ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .style(Style::default().bg(Color::Black));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let buckets = app.analytics.buckets();
+    if buckets.is_empty() {
+        let empty = Paragraph::new("  No suggestions recorded yet")
+            .style(Style::default().fg(Color::Gray));
+        frame.render_widget(empty, inner);
+        return;
+    }
+
+    let header = Line::from(Span::styled(
+        format!(
+            " {:<6} {:<10} {:>10} {:>7} {:>9} {:>8} {:>8} {:>7} {:>11}",
+            "Source", "Provider", "Confidence", "Shown", "Accepted", "Partial", "Ignored", "Rate", "Calibrated"
+        ),
+        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD),
+    ));
+    let mut lines = vec![header];
+
+    let (mut shown, mut used) = (0, 0.0);
+    for (key, stats) in &buckets {
+        let source = match key.source {
+            SuggestionSource::Local => "Local",
+            SuggestionSource::Api => "API",
+        };
+        let rate = stats.acceptance_rate().unwrap_or_default();
+        let calibrated = app.analytics.calibrate(key.source, key.provider, key.confidence());
+        let color = if rate >= key.confidence() { Color::Green } else { Color::White };
+        lines.push(Line::from(Span::styled(
+            format!(
+                " {:<6} {:<10} {:>4.1}-{:<4.1} {:>7} {:>9} {:>8} {:>8} {:>6.0}% {:>11.2}",
+                source,
+                key.provider.to_string(),
+                key.bucket as f32 / 10.0,
+                (key.bucket + 1) as f32 / 10.0,
+                stats.shown,
+                stats.accepted,
+                stats.partial,
+                stats.ignored,
+                rate * 100.0,
+                calibrated,
+            ),
+            Style::default().fg(color),
+        )));
+        shown += stats.shown;
+        used += stats.accepted as f32 + 0.5 * stats.partial as f32;
+    }
+
+    lines.push(Line::default());
+    lines.push(Line::from(Span::styled(
+        format!(
+            " {} suggestions shown, {:.0}% used (partial counts as half)",
+            shown,
+            used / shown.max(1) as f32 * 100.0
+        ),
+        Style::default().fg(Color::Gray),
+    )));
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}